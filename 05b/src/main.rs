@@ -171,3 +171,50 @@ fn main() {
   println!("{:?}", memout);
   println!("{:?}", computer.get_output());
 }
+
+#[cfg(test)]
+mod tests {
+  use intcode::{conformance, Feature, Termination, Unit, Vm};
+  use super::int_code::Computer;
+
+  /// Day 5's computer takes all of its input up front and runs to the end
+  struct Day5 {
+    memory: Vec<i32>,
+    input: Vec<i32>,
+    output: Vec<i32>
+  }
+
+  impl Vm for Day5 {
+    fn load(program: &[Unit]) -> Day5 {
+      Day5 { memory: program.iter().map(|value| *value as i32).collect(), input: Vec::new(), output: Vec::new() }
+    }
+
+    fn supports(_: Feature) -> bool {
+      false
+    }
+
+    fn push_input(&mut self, value: Unit) {
+      self.input.push(value as i32);
+    }
+
+    fn run(&mut self) -> Termination {
+      let mut computer = Computer::new(&self.memory, &self.input);
+      self.memory = computer.run();
+      self.output.extend(computer.get_output());
+      Termination::Halted
+    }
+
+    fn take_output(&mut self) -> Vec<Unit> {
+      self.output.drain(..).map(Unit::from).collect()
+    }
+
+    fn peek(&self, address: usize) -> Unit {
+      self.memory[address] as Unit
+    }
+  }
+
+  #[test]
+  fn test_conformance() {
+    assert_eq!(conformance::<Day5>().map(|_| ()), Ok(()));
+  }
+}
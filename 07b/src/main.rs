@@ -250,6 +250,47 @@ mod int_code {
             &self.state
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use intcode::{conformance, Feature, Termination, Unit, Vm};
+        use super::{Computer, State};
+
+        impl Vm for Computer {
+            fn load(program: &[Unit]) -> Computer {
+                Computer::new(&program.iter().map(|value| *value as i32).collect(), None)
+            }
+
+            fn supports(feature: Feature) -> bool {
+                feature == Feature::Blocking
+            }
+
+            fn push_input(&mut self, value: Unit) {
+                Computer::push_input(self, value as i32);
+            }
+
+            fn run(&mut self) -> Termination {
+                match Computer::run(self) {
+                    State::Halted => Termination::Halted,
+                    State::Blocked => Termination::Blocked,
+                    state => Termination::Failed(format!("stopped {:?}", state)),
+                }
+            }
+
+            fn take_output(&mut self) -> Vec<Unit> {
+                self.output.drain(..).map(Unit::from).collect()
+            }
+
+            fn peek(&self, address: usize) -> Unit {
+                self.memory[address] as Unit
+            }
+        }
+
+        #[test]
+        fn test_conformance() {
+            assert_eq!(conformance::<Computer>().map(|_| ()), Ok(()));
+        }
+    }
 }
 
 fn main() {
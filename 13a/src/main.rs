@@ -1,10 +1,8 @@
 use std::collections::HashMap;
 
-use crate::int_code::Computer;
+use intcode::Computer;
 use crate::Tile::{Empty, Wall, Block, HorizontalPaddle, Ball};
-use crate::int_code::State::Halted;
-
-mod int_code;
+use intcode::State::Halted;
 
 #[derive(PartialEq)]
 enum Tile {
@@ -29,10 +27,10 @@ impl From<i32> for Tile {
 }
 
 fn main() {
-    let memory = int_code::read_memory().unwrap();
+    let memory = intcode::read_memory().unwrap();
     let mut computer = Computer::new(&memory, None);
 
-    match computer.run() {
+    match computer.run().unwrap() {
         Halted => (),
         state => panic!("Invalid computer state: {:?}", state)
     }
@@ -40,9 +38,9 @@ fn main() {
     let mut canvas: HashMap<(i32, i32), Tile> = HashMap::new();
 
     while !computer.get_output().is_empty() {
-        let x = computer.pop_output() as i32;
-        let y = computer.pop_output() as i32;
-        let tile = Tile::from(computer.pop_output() as i32);
+        let x = computer.pop_output().unwrap() as i32;
+        let y = computer.pop_output().unwrap() as i32;
+        let tile = Tile::from(computer.pop_output().unwrap() as i32);
 
         canvas.insert((x, y), tile);
     }
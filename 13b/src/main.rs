@@ -2,11 +2,9 @@ use std::collections::HashMap;
 use std::cmp::{max, Ordering};
 use std::{thread, time};
 
-mod int_code;
-
-use crate::int_code::{Computer, Unit};
+use intcode::{Computer, Unit};
 use crate::Tile::{Empty, Wall, Block, HorizontalPaddle, Ball};
-use crate::int_code::State;
+use intcode::State;
 
 type Canvas = HashMap<(i32, i32), Tile>;
 
@@ -85,7 +83,7 @@ fn move_paddle(canvas: &Canvas) -> Unit {
 }
 
 fn main() {
-    let mut memory = int_code::read_memory().unwrap();
+    let mut memory = intcode::read_memory().unwrap();
     memory[0] = 2;
     let mut computer = Computer::new(&memory, None);
 
@@ -93,12 +91,12 @@ fn main() {
     let mut score = 0;
 
     loop {
-        computer.run();
+        computer.run().unwrap();
 
         while !computer.get_output().is_empty() {
-            let x = computer.pop_output() as i32;
-            let y = computer.pop_output() as i32;
-            let v = computer.pop_output() as i32;
+            let x = computer.pop_output().unwrap() as i32;
+            let y = computer.pop_output().unwrap() as i32;
+            let v = computer.pop_output().unwrap() as i32;
 
             let tile = match (x, y) {
                 (-1, 0) => { score = v; continue; },
@@ -1,9 +1,7 @@
-mod int_code;
-
 use std::convert::{TryFrom, TryInto};
 use std::collections::{VecDeque, HashSet};
 
-use crate::int_code::Computer;
+use intcode::Computer;
 use crate::Direction::*;
 use crate::Status::*;
 use crate::Error::ParseStatusError;
@@ -21,8 +19,8 @@ enum Direction {
     East
 }
 
-impl Into<int_code::Unit> for Direction {
-    fn into(self) -> int_code::Unit {
+impl Into<intcode::Unit> for Direction {
+    fn into(self) -> intcode::Unit {
         match self {
             North => 1,
             South => 2,
@@ -38,10 +36,10 @@ enum Status {
     FoundOxygen,
 }
 
-impl TryFrom<int_code::Unit> for Status {
+impl TryFrom<intcode::Unit> for Status {
     type Error = Error;
 
-    fn try_from(value: int_code::Unit) -> Result<Self, Self::Error> {
+    fn try_from(value: intcode::Unit) -> Result<Self, Self::Error> {
         match value {
             0 => Ok(HitWall),
             1 => Ok(Moved),
@@ -77,7 +75,7 @@ impl From<(i32, i32)> for Position {
 type Distance = i32;
 
 fn main() {
-    let memory = int_code::read_memory().unwrap();
+    let memory = intcode::read_memory().unwrap();
     let computer = Computer::new(&memory, None);
 
     let mut seen: HashSet<Position> = HashSet::new();
@@ -99,11 +97,11 @@ fn main() {
             let mut comp_clone = comp.clone();
             comp_clone.push_input(direction.into());
 
-            if let int_code::State::Halted = comp_clone.run() {
+            if let intcode::State::Halted = comp_clone.run().unwrap() {
                 panic!("Computer halted");
             }
 
-            let status: Status = comp_clone.pop_output().try_into().unwrap();
+            let status: Status = comp_clone.pop_output().unwrap().try_into().unwrap();
             seen.insert(new_pos);
 
             match status {
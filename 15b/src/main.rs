@@ -1,9 +1,7 @@
-mod int_code;
-
 use std::convert::{TryFrom, TryInto};
 use std::collections::{VecDeque, HashSet};
 
-use crate::int_code::Computer;
+use intcode::Computer;
 use crate::Direction::*;
 use crate::Status::*;
 use crate::Error::ParseStatusError;
@@ -21,8 +19,8 @@ enum Direction {
     East
 }
 
-impl Into<int_code::Unit> for Direction {
-    fn into(self) -> int_code::Unit {
+impl Into<intcode::Unit> for Direction {
+    fn into(self) -> intcode::Unit {
         match self {
             North => 1,
             South => 2,
@@ -39,10 +37,10 @@ enum Status {
     FoundOxygen,
 }
 
-impl TryFrom<int_code::Unit> for Status {
+impl TryFrom<intcode::Unit> for Status {
     type Error = Error;
 
-    fn try_from(value: int_code::Unit) -> Result<Self, Self::Error> {
+    fn try_from(value: intcode::Unit) -> Result<Self, Self::Error> {
         match value {
             0 => Ok(HitWall),
             1 => Ok(Moved),
@@ -98,11 +96,11 @@ fn bfs(initial_computer: Computer) -> Vec<(Distance, Status, Computer)> {
             let mut new_computer = computer.clone();
             new_computer.push_input(direction.into());
 
-            if let int_code::State::Halted = new_computer.run() {
+            if let intcode::State::Halted = new_computer.run().unwrap() {
                 panic!("Computer halted");
             }
 
-            let status: Status = new_computer.pop_output().try_into().unwrap();
+            let status: Status = new_computer.pop_output().unwrap().try_into().unwrap();
             seen.insert(new_pos);
             let new_distance = distance + 1;
             result.push((new_distance, status, new_computer.clone()));
@@ -118,7 +116,7 @@ fn bfs(initial_computer: Computer) -> Vec<(Distance, Status, Computer)> {
 }
 
 fn main() {
-    let memory = int_code::read_memory().unwrap();
+    let memory = intcode::read_memory().unwrap();
     let computer = Computer::new(&memory, None);
 
     let result_from_start = bfs(computer);
@@ -1,9 +1,7 @@
-use crate::int_code::Computer;
+use intcode::{AsciiTerminal, Computer};
 use std::collections::HashMap;
 use std::cmp::max;
 
-mod int_code;
-
 #[derive(PartialOrd, PartialEq, Eq, Hash, Copy, Clone, Debug)]
 struct Position {
     x: i32,
@@ -25,17 +23,15 @@ fn is_intersection(map: &HashMap<Position, char>, position: &Position) -> bool {
 }
 
 fn main() {
-    let memory = int_code::read_memory().unwrap();
-    let mut computer = Computer::new(&memory, None);
-    computer.run();
+    let memory = intcode::read_memory().unwrap();
+    let mut terminal = AsciiTerminal::new(Computer::new(&memory, None));
+    let output = terminal.read_until_prompt().unwrap();
 
     let mut position: Position = (0, 0).into();
     let mut map: HashMap<Position, char> = HashMap::new();
     let mut max_pos = position;
 
-    for c in computer.get_output() {
-        let c: char = (*c as u8) as char;
-
+    for c in output.chars() {
         max_pos = (max(position.x, max_pos.x), max(position.y, max_pos.y)).into();
 
         if c == '\n' {
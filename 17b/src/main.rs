@@ -1,9 +1,7 @@
-use crate::int_code::{Computer, Unit};
+use intcode::{AsciiTerminal, Computer};
 use std::collections::HashMap;
 use std::cmp::max;
 
-mod int_code;
-
 use Direction::*;
 use Turn::*;
 use std::convert::TryFrom;
@@ -163,15 +161,13 @@ fn find_solution(path: &Vec<PathElement>) -> Option<(Vec<char>, HashMap<char, Pa
     None
 }
 
-fn read_map(computer: &mut Computer) -> HashMap<Position, char> {
+fn read_map(output: &str) -> HashMap<Position, char> {
     let mut position: Position = (0, 0).into();
     let mut map: HashMap<Position, char> = HashMap::new();
     let mut max_pos = position;
 
     let mut last_c = '.';
-    for c in computer.get_output() {
-        let c: char = (*c as u8) as char;
-
+    for c in output.chars() {
         max_pos = (max(position.x, max_pos.x), max(position.y, max_pos.y)).into();
 
         match (c, last_c) {
@@ -252,22 +248,22 @@ fn build_main_routine(path: &Vec<PathElement>, a: usize, alen: usize, b: usize,
 }
 
 fn main() {
-    let mut memory = int_code::read_memory().unwrap();
+    let mut memory = intcode::read_memory().unwrap();
     memory[0] = 2;
     let memory = memory;
-    let mut computer = Computer::new(&memory, None);
-    computer.run();
+    let mut terminal = AsciiTerminal::new(Computer::new(&memory, None));
+    let output = terminal.read_until_prompt().unwrap();
 
-    let map = read_map(&mut computer);
+    let map = read_map(&output);
     let path = build_path(&map);
     let solution = find_solution(&path);
     let (main_routine, functions) = solution.unwrap();
     let solution_input = build_solution_input(main_routine, functions);
 
-    solution_input.chars()
-        .for_each(|c| computer.push_input(c as Unit));
+    solution_input.lines()
+        .for_each(|line| terminal.send_line(line));
 
-    computer.run();
+    terminal.read_until_prompt().unwrap();
 
-    println!("{}", computer.get_output().back().unwrap());
+    println!("{}", terminal.answer().unwrap());
 }
\ No newline at end of file
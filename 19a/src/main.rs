@@ -1,9 +1,7 @@
-mod int_code;
-
-use int_code::Computer;
+use intcode::Computer;
 
 fn main() {
-    let memory = int_code::read_memory().unwrap();
+    let memory = intcode::read_memory().unwrap();
 
     let mut count = 0;
 
@@ -12,9 +10,9 @@ fn main() {
             let mut computer = Computer::new(&memory, None);
             computer.push_input(y);
             computer.push_input(x);
-            computer.run();
+            computer.run().unwrap();
 
-            if computer.pop_output() == 1 {
+            if computer.pop_output().unwrap() == 1 {
                 count += 1;
             }
         }
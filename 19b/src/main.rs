@@ -1,10 +1,8 @@
-mod int_code;
-
 use std::io::{self, BufRead};
 
-use int_code::Computer;
+use intcode::Computer;
 use crate::RowEvaluation::*;
-use crate::int_code::Unit;
+use intcode::Unit;
 use std::collections::{HashMap, HashSet};
 
 enum RowEvaluation {
@@ -79,13 +77,13 @@ fn read_input() -> HashSet<(usize, usize)> {
 }
 
 fn main() {
-    let memory = int_code::read_memory().unwrap();
+    let memory = intcode::read_memory().unwrap();
 
     let is_pulled = |x, y| {
         let mut computer = Computer::new(&memory, Some(&vec![x as Unit, y as Unit]));
-        computer.run();
+        computer.run().unwrap();
 
-        return computer.pop_output() == 1;
+        return computer.pop_output().unwrap() == 1;
     };
 
 //    let map = read_input();
@@ -1,31 +1,24 @@
-use crate::int_code::Computer;
-
-mod int_code;
+use intcode::{AsciiTerminal, Computer};
 
 fn main() {
-    let memory = int_code::read_memory().unwrap();
-    let mut computer = Computer::new(&memory, None);
+    let memory = intcode::read_memory().unwrap();
+    let mut terminal = AsciiTerminal::new(Computer::new(&memory, None));
 
-    computer.println("NOT A J".into());
-    computer.println("NOT B T".into());
-    computer.println( "OR T J".into());
-    computer.println("NOT C T".into());
-    computer.println( "OR T J".into());
-    computer.println("AND D J".into());
-    computer.println("NOT J T".into());
-    computer.println("OR H T".into());
-    computer.println("OR E T".into());
-    computer.println("AND T J".into());
-    computer.println("RUN".into());
+    terminal.send_line("NOT A J");
+    terminal.send_line("NOT B T");
+    terminal.send_line( "OR T J");
+    terminal.send_line("NOT C T");
+    terminal.send_line( "OR T J");
+    terminal.send_line("AND D J");
+    terminal.send_line("NOT J T");
+    terminal.send_line("OR H T");
+    terminal.send_line("OR E T");
+    terminal.send_line("AND T J");
+    terminal.send_line("RUN");
 
-    computer.run();
+    print!("{}", terminal.read_until_prompt().unwrap());
 
-    for value in computer.get_output() {
-        if *value < 128 {
-            print!("{}", (*value as u8) as char);
-        } else {
-            eprintln!("{}", value);
-        }
+    for answer in terminal.answers() {
+        eprintln!("{}", answer);
     }
-
 }
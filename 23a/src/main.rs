@@ -1,37 +1,14 @@
-mod int_code;
-
-use crate::int_code::Computer;
+use intcode::{NatAction, NatEvent, Network};
 
 fn main() {
-    let memory = int_code::read_memory().unwrap();
-    let mut computers: Vec<Computer> = (0..50)
-        .map(|i| Computer::new(&memory, Some(&vec![i])))
-        .collect();
-    computers
-        .iter_mut()
-        .for_each(|c| { c.run(); } );
-
-    'outer: loop {
-        for i in 0..50 {
-            while !computers[i].get_output().is_empty() {
-                let j = computers[i].pop_output() as usize;
-                let x = computers[i].pop_output();
-                let y = computers[i].pop_output();
-
-                if j == 255 {
-                    println!("{}", y);
-                    break 'outer;
-                }
-
-                computers[j].push_input(x);
-                computers[j].push_input(y);
-            }
+    let memory = intcode::read_memory().unwrap();
+    let mut network = Network::new(&memory, 50);
 
-            if computers[i].get_input().is_empty() {
-                computers[i].push_input(-1);
-            }
+    network.set_nat(255, |event| match event {
+        NatEvent::Packet(packet) => NatAction::Stop(packet.clone()),
+        NatEvent::Idle => NatAction::Continue,
+    });
 
-            computers[i].run();
-        }
-    }
+    let packet = network.run().unwrap().expect("Network stopped without a packet to the NAT");
+    println!("{}", packet.values[1]);
 }
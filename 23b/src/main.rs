@@ -1,51 +1,26 @@
-mod int_code;
-
-use crate::int_code::{Computer, Unit};
+use intcode::{NatAction, NatEvent, Network, Packet, Unit};
 
 fn main() {
-    let memory = int_code::read_memory().unwrap();
-    let mut computers: Vec<Computer> = (0..50)
-        .map(|i| Computer::new(&memory, Some(&vec![i])))
-        .collect();
-    computers
-        .iter_mut()
-        .for_each(|c| { c.run(); } );
-    let mut nat: Option<(Unit, Unit)> = None;
-    let mut delivered_nat: Option<(Unit, Unit)> = None;
-
-    'outer: loop {
-        if nat.is_some() && computers.iter().all(|c| c.get_output().is_empty()) {
-            computers[0].push_input(nat.unwrap().0);
-            computers[0].push_input(nat.unwrap().1);
-
-            if delivered_nat.is_some() && delivered_nat.unwrap().1 == nat.unwrap().1 {
-                println!("{}", nat.unwrap().1);
-                break 'outer;
-            }
-
-            delivered_nat = nat;
-            nat = None;
+    let memory = intcode::read_memory().unwrap();
+    let mut nat: Option<Packet> = None;
+    let mut delivered_y: Option<Unit> = None;
+    let mut network = Network::new(&memory, 50);
+
+    network.set_nat(255, |event| match event {
+        NatEvent::Packet(packet) => {
+            nat = Some(packet.clone());
+            NatAction::Continue
         }
-
-        for i in 0..50 {
-            while !computers[i].get_output().is_empty() {
-                let j = computers[i].pop_output() as usize;
-                let x = computers[i].pop_output();
-                let y = computers[i].pop_output();
-
-                if j == 255 {
-                    nat = Some((x, y));
-                } else {
-                    computers[j].push_input(x);
-                    computers[j].push_input(y);
-                }
-            }
-
-            if computers[i].get_input().is_empty() {
-                computers[i].push_input(-1);
+        NatEvent::Idle => match nat.take() {
+            Some(packet) if delivered_y == Some(packet.values[1]) => NatAction::Stop(packet),
+            Some(packet) => {
+                delivered_y = Some(packet.values[1]);
+                NatAction::Send(Packet { source: 255, destination: 0, values: packet.values })
             }
-
-            computers[i].run();
+            None => NatAction::Continue,
         }
-    }
+    });
+
+    let packet = network.run().unwrap().expect("Network stopped without a repeated NAT packet");
+    println!("{}", packet.values[1]);
 }
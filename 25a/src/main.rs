@@ -1,9 +1,6 @@
-use crate::int_code::{Computer, Unit};
-use crate::int_code::State::Halted;
+use intcode::{AsciiTerminal, Computer, Unit};
 use std::io;
-use std::fs;
-
-mod int_code;
+use std::fs::{self, File};
 
 fn main() {
     let memory = fs::read_to_string("input.txt").expect("Failed to read file: input.txt");
@@ -12,23 +9,31 @@ fn main() {
         .map(str::trim)
         .map(|s| s.parse().unwrap())
         .collect();
-//    let memory = int_code::read_memory().unwrap();
-    let mut computer = Computer::new(&memory, None);
+//    let memory = intcode::read_memory().unwrap();
+    let mut terminal = AsciiTerminal::new(Computer::new(&memory, None));
 
-    while *computer.get_state() != Halted {
-        computer.run();
+    while !terminal.is_halted() {
+        print!("{}", terminal.read_until_prompt().unwrap());
 
-        while let Some(c) = computer.pop_output() {
-            print!("{}", (c as u8) as char);
+        let mut line: String = String::new();
+        if io::stdin().read_line(&mut line).expect("Failed to read input") == 0 {
+            break;
         }
 
-        let mut line: String = String::new();
-        io::stdin().read_line(&mut line).expect("Failed to read input");
+        if let Some(path) = line.trim().strip_prefix("!save ") {
+            let mut file = File::create(path).expect("Failed to create save file");
+            terminal.computer().save(&mut file).expect("Failed to save computer");
+            println!("Saved to {}", path);
+            continue;
+        }
 
-        for c in line.chars() {
-            computer.push_input(c as Unit);
+        if let Some(path) = line.trim().strip_prefix("!load ") {
+            let file = File::open(path).expect("Failed to open save file");
+            terminal = AsciiTerminal::new(Computer::load(file).expect("Failed to load computer"));
+            println!("Loaded from {}", path);
+            continue;
         }
 
-//        computer.push_input(10);
+        terminal.send_line(line.trim_end_matches('\n'));
     }
 }
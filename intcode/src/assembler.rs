@@ -0,0 +1,698 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::{AddressingMode, Instruction, Op, Operand, Unit};
+
+use AddressingMode::{Absolute, Immediate, Relative};
+
+const MAX_MACRO_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct AssemblerError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AssemblerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for AssemblerError {}
+
+fn error<T>(line: usize, message: String) -> Result<T, AssemblerError> {
+    Err(AssemblerError { line, message })
+}
+
+/// A value that may refer to a label or `.equ` symbol: `sign * symbol + offset`.
+#[derive(Debug, Clone)]
+struct Expr {
+    symbol: Option<String>,
+    sign: Unit,
+    offset: Unit,
+}
+
+impl Expr {
+    fn value(value: Unit) -> Expr {
+        Expr { symbol: None, sign: 1, offset: value }
+    }
+
+    fn negate(self, line: usize) -> Result<Expr, AssemblerError> {
+        match self.offset.checked_neg() {
+            Some(offset) => Ok(Expr { symbol: self.symbol, sign: -self.sign, offset }),
+            None => error(line, format!("Value out of range: -({})", self.offset)),
+        }
+    }
+
+    fn resolve(&self, symbols: &HashMap<String, Unit>, line: usize) -> Result<Unit, AssemblerError> {
+        match &self.symbol {
+            Some(symbol) => match symbols.get(symbol) {
+                Some(value) => match self.sign.checked_mul(*value).and_then(|value| value.checked_add(self.offset)) {
+                    Some(value) => Ok(value),
+                    None => error(line, format!("Value out of range: {}{:+}", symbol, self.offset)),
+                },
+                None => error(line, format!("Undefined symbol: {}", symbol)),
+            },
+            None => Ok(self.offset),
+        }
+    }
+}
+
+enum Item {
+    Instruction(Op, Vec<(AddressingMode, Expr)>),
+    Data(Vec<Expr>),
+}
+
+impl Item {
+    fn size(&self) -> usize {
+        match self {
+            Item::Instruction(_, operands) => operands.len() + 1,
+            Item::Data(values) => values.len(),
+        }
+    }
+}
+
+struct Line {
+    number: usize,
+    text: String,
+}
+
+struct Macro {
+    params: Vec<String>,
+    body: Vec<String>,
+}
+
+fn unescape(c: char) -> char {
+    match c {
+        'n' => '\n',
+        't' => '\t',
+        '0' => '\0',
+        c => c,
+    }
+}
+
+/// Splits an operand list on whitespace and commas, keeping bracketed
+/// operands and quoted literals together.
+fn tokenize(text: &str, line: usize) -> Result<Vec<String>, AssemblerError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() || c == ',' {
+            chars.next();
+            continue;
+        }
+
+        let mut token = String::new();
+
+        match c {
+            '"' | '\'' => {
+                token.push(chars.next().unwrap());
+
+                loop {
+                    match chars.next() {
+                        Some('\\') => {
+                            token.push('\\');
+                            token.extend(chars.next());
+                        }
+                        Some(q) if q == c => {
+                            token.push(q);
+                            break;
+                        }
+                        Some(other) => token.push(other),
+                        None => return error(line, format!("Unterminated literal: {}", token)),
+                    }
+                }
+            }
+            _ => {
+                let mut depth = 0;
+
+                while let Some(&c) = chars.peek() {
+                    match c {
+                        '[' => depth += 1,
+                        ']' => depth -= 1,
+                        c if depth == 0 && (c.is_whitespace() || c == ',') => break,
+                        _ => (),
+                    }
+
+                    token.push(c);
+                    chars.next();
+                }
+
+                if depth != 0 {
+                    return error(line, format!("Unbalanced brackets: {}", token));
+                }
+            }
+        }
+
+        if token.starts_with("->") && token.len() > 2 {
+            tokens.push(String::from("->"));
+            tokens.push(token[2..].to_string());
+        } else {
+            tokens.push(token);
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn parse_literal(token: &str, line: usize) -> Result<Vec<char>, AssemblerError> {
+    let quote = match token.chars().next() {
+        Some(c) if token.len() >= 2 && token.ends_with(c) => c,
+        _ => return error(line, format!("Unterminated literal: {}", token)),
+    };
+
+    let inner = &token[quote.len_utf8()..token.len() - quote.len_utf8()];
+    let mut chars = inner.chars();
+    let mut result = Vec::new();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(c) => result.push(unescape(c)),
+                None => return error(line, format!("Dangling escape in {}", token)),
+            },
+            c => result.push(c),
+        }
+    }
+
+    Ok(result)
+}
+
+fn is_symbol(text: &str) -> bool {
+    let mut chars = text.chars();
+
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '.' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        }
+        _ => false,
+    }
+}
+
+fn parse_expr(text: &str, line: usize) -> Result<Expr, AssemblerError> {
+    let text = text.trim();
+
+    if text.starts_with('\'') {
+        return match parse_literal(text, line)?.as_slice() {
+            [c] => Ok(Expr::value(*c as Unit)),
+            _ => error(line, format!("Character literal must hold one character: {}", text)),
+        };
+    }
+
+    if let Ok(value) = text.parse::<Unit>() {
+        return Ok(Expr::value(value));
+    }
+
+    let (symbol, offset) = match text.char_indices().skip(1).find(|(_, c)| *c == '+' || *c == '-') {
+        Some((index, _)) => text.split_at(index),
+        None => (text, ""),
+    };
+    let symbol = symbol.trim();
+
+    if !is_symbol(symbol) {
+        return error(line, format!("Invalid expression: {}", text));
+    }
+
+    let offset = match offset.replace(' ', "").as_str() {
+        "" => 0,
+        offset => offset.trim_start_matches('+').parse::<Unit>()
+            .or_else(|_| error(line, format!("Invalid offset in expression: {}", text)))?,
+    };
+
+    Ok(Expr { symbol: Some(symbol.to_string()), sign: 1, offset })
+}
+
+fn parse_operand(token: &str, line: usize) -> Result<(AddressingMode, Expr), AssemblerError> {
+    if let Some(immediate) = token.strip_prefix('#') {
+        return Ok((Immediate, parse_expr(immediate, line)?));
+    }
+
+    if token.starts_with('[') && token.ends_with(']') {
+        let inner = token[1..token.len() - 1].trim();
+
+        if inner == "rb" {
+            return Ok((Relative, Expr::value(0)));
+        }
+
+        if let Some(rest) = inner.strip_prefix("rb") {
+            let rest = rest.trim_start();
+
+            if let Some(offset) = rest.strip_prefix('+') {
+                return Ok((Relative, parse_expr(offset, line)?));
+            }
+
+            if let Some(offset) = rest.strip_prefix('-') {
+                return Ok((Relative, parse_expr(offset, line)?.negate(line)?));
+            }
+        }
+
+        return Ok((Absolute, parse_expr(inner, line)?));
+    }
+
+    error(line, format!("Operand must be #value, [address] or [rb+offset]: {}", token))
+}
+
+/// Operand syntax for macro arguments, where a bare value means immediate.
+fn as_operand(token: &str) -> String {
+    if token.starts_with('#') || token.starts_with('[') {
+        token.to_string()
+    } else {
+        format!("#{}", token)
+    }
+}
+
+fn builtin_macro(name: &str, args: &[String], unique: usize) -> Option<Vec<String>> {
+    let expansion = match (name, args) {
+        ("call", [target]) => vec![
+            format!("ADD #__call_{} #0 -> [rb+1]", unique),
+            String::from("REL #1"),
+            format!("JZ #0 {}", as_operand(target)),
+            format!("__call_{}:", unique),
+        ],
+        ("ret", []) => vec![
+            String::from("REL #-1"),
+            String::from("JZ #0 [rb+1]"),
+        ],
+        ("push", [value]) => vec![
+            format!("ADD {} #0 -> [rb+1]", as_operand(value)),
+            String::from("REL #1"),
+        ],
+        ("pop", [dest]) => vec![
+            format!("ADD [rb+0] #0 -> {}", dest),
+            String::from("REL #-1"),
+        ],
+        ("jmp", [target]) => vec![
+            format!("JZ #0 {}", as_operand(target)),
+        ],
+        _ => return None,
+    };
+
+    Some(expansion)
+}
+
+fn strip_comment(text: &str) -> &str {
+    let mut quote = None;
+
+    for (index, c) in text.char_indices() {
+        match (quote, c) {
+            (None, ';') => return &text[..index],
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            _ => (),
+        }
+    }
+
+    text
+}
+
+fn split_mnemonic(text: &str) -> (&str, &str) {
+    match text.find(char::is_whitespace) {
+        Some(index) => (&text[..index], text[index..].trim()),
+        None => (text, ""),
+    }
+}
+
+fn collect_macros(source: &str) -> Result<(Vec<Line>, HashMap<String, Macro>), AssemblerError> {
+    let mut lines = Vec::new();
+    let mut macros = HashMap::new();
+    let mut current: Option<(String, Macro)> = None;
+
+    for (index, text) in source.lines().enumerate() {
+        let number = index + 1;
+        let stripped = strip_comment(text).trim();
+        let (directive, rest) = split_mnemonic(stripped);
+
+        match (directive.to_ascii_lowercase().as_str(), current.as_mut()) {
+            (".macro", None) => {
+                let mut params = tokenize(rest, number)?;
+
+                if params.is_empty() {
+                    return error(number, String::from("Macro needs a name"));
+                }
+
+                let name = params.remove(0).to_ascii_lowercase();
+                current = Some((name, Macro { params, body: Vec::new() }));
+            }
+            (".macro", Some(_)) => return error(number, String::from("Nested macro definitions are not supported")),
+            (".endm", Some(_)) => {
+                let (name, definition) = current.take().unwrap();
+                macros.insert(name, definition);
+            }
+            (".endm", None) => return error(number, String::from(".endm without .macro")),
+            (_, Some((_, definition))) => definition.body.push(stripped.to_string()),
+            (_, None) => lines.push(Line { number, text: stripped.to_string() }),
+        }
+    }
+
+    if let Some((name, _)) = current {
+        return error(source.lines().count(), format!("Unterminated macro: {}", name));
+    }
+
+    Ok((lines, macros))
+}
+
+/// Replaces `\PARAM` with the argument for a whole parameter name and `\@`
+/// with `unique` in a line of a macro body, leaving literals alone.
+fn substitute(text: &str, params: &[String], args: &[String], unique: usize) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars().peekable();
+    let mut quote = None;
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(_), '\\') => {
+                result.push(c);
+                result.extend(chars.next());
+            }
+            (Some(q), c) => {
+                if c == q {
+                    quote = None;
+                }
+
+                result.push(c);
+            }
+            (None, '"') | (None, '\'') => {
+                quote = Some(c);
+                result.push(c);
+            }
+            (None, '\\') if chars.peek() == Some(&'@') => {
+                chars.next();
+                result.push_str(&unique.to_string());
+            }
+            (None, '\\') => {
+                let mut name = String::new();
+
+                while let Some(&c) = chars.peek().filter(|c| c.is_ascii_alphanumeric() || **c == '_') {
+                    name.push(c);
+                    chars.next();
+                }
+
+                match params.iter().position(|param| *param == name) {
+                    Some(index) => result.push_str(&args[index]),
+                    None => {
+                        result.push('\\');
+                        result.push_str(&name);
+                    }
+                }
+            }
+            (None, c) => result.push(c),
+        }
+    }
+
+    result
+}
+
+fn expand(line: Line, macros: &HashMap<String, Macro>, unique: &mut usize, depth: usize, out: &mut Vec<Line>) -> Result<(), AssemblerError> {
+    let number = line.number;
+    let (label, rest) = split_label(&line.text);
+    let (name, args) = split_mnemonic(rest);
+    let name = name.to_ascii_lowercase();
+
+    let body = match macros.get(&name) {
+        Some(definition) => {
+            let args = tokenize(args, number)?;
+
+            if args.len() != definition.params.len() {
+                return error(number, format!("Macro {} expects {} arguments, got {}", name, definition.params.len(), args.len()));
+            }
+
+            *unique += 1;
+
+            definition.body.iter()
+                .map(|text| substitute(text, &definition.params, &args, *unique))
+                .collect()
+        }
+        None => match builtin_macro(&name, &tokenize(args, number)?, *unique + 1) {
+            Some(body) => {
+                *unique += 1;
+                body
+            }
+            None => {
+                out.push(line);
+                return Ok(());
+            }
+        },
+    };
+
+    if depth >= MAX_MACRO_DEPTH {
+        return error(number, format!("Macro expansion too deep in {}", name));
+    }
+
+    if let Some(label) = label {
+        out.push(Line { number, text: format!("{}:", label) });
+    }
+
+    for text in body {
+        expand(Line { number, text }, macros, unique, depth + 1, out)?;
+    }
+
+    Ok(())
+}
+
+/// Splits a leading `label:` off a line, if there is one.
+fn split_label(text: &str) -> (Option<&str>, &str) {
+    match text.find(':') {
+        Some(index) if is_symbol(text[..index].trim()) => (Some(text[..index].trim()), text[index + 1..].trim()),
+        _ => (None, text),
+    }
+}
+
+/// Removes the address column of a disassembler listing.
+fn strip_address(text: &str) -> &str {
+    let (first, rest) = split_mnemonic(text);
+
+    if !first.is_empty() && first.chars().all(|c| c.is_ascii_digit()) {
+        rest
+    } else {
+        text
+    }
+}
+
+fn parse_item(text: &str, number: usize, symbols: &mut HashMap<String, Unit>) -> Result<Option<Item>, AssemblerError> {
+    let (mnemonic, rest) = split_mnemonic(text);
+
+    match mnemonic.to_ascii_lowercase().as_str() {
+        "" => Ok(None),
+        ".data" => {
+            let mut values = Vec::new();
+
+            for token in tokenize(rest, number)? {
+                if token.starts_with('"') {
+                    values.extend(parse_literal(&token, number)?.into_iter().map(|c| Expr::value(c as Unit)));
+                } else {
+                    values.push(parse_expr(&token, number)?);
+                }
+            }
+
+            Ok(Some(Item::Data(values)))
+        }
+        ".equ" => match tokenize(rest, number)?.as_slice() {
+            [name, value] if is_symbol(name) => {
+                let value = parse_expr(value, number)?.resolve(symbols, number)?;
+
+                if symbols.insert(name.to_string(), value).is_some() {
+                    return error(number, format!("Duplicate symbol: {}", name));
+                }
+
+                Ok(None)
+            }
+            _ => error(number, format!("Expected .equ NAME, VALUE: {}", text)),
+        },
+        _ => {
+            let op = match Op::from_mnemonic(mnemonic) {
+                Some(op) => op,
+                None => return error(number, format!("Unknown mnemonic: {}", mnemonic)),
+            };
+            let (num_params, has_dest) = op.num_params();
+
+            let mut tokens = tokenize(rest, number)?;
+
+            if let Some(index) = tokens.iter().position(|token| token == "->") {
+                if !has_dest || index + 2 != tokens.len() {
+                    return error(number, format!("Misplaced -> in {}", text));
+                }
+
+                tokens.remove(index);
+            }
+
+            if tokens.len() != num_params {
+                return error(number, format!("{} expects {} operands, got {}", op.mnemonic(), num_params, tokens.len()));
+            }
+
+            let operands = tokens.iter()
+                .map(|token| parse_operand(token, number))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            if has_dest && operands[num_params - 1].0 == Immediate {
+                return error(number, format!("Destination cannot be immediate: {}", text));
+            }
+
+            Ok(Some(Item::Instruction(op, operands)))
+        }
+    }
+}
+
+/// Assembles Intcode source into a memory image.
+///
+/// The syntax is the one produced by the disassembler: one instruction per
+/// line, `#value`, `[address]` and `[rb+offset]` operands, an optional `->`
+/// before the destination and `label:` definitions. Leading address columns
+/// and `;` comments are ignored. The directives are `.data` (integers,
+/// symbols, `'c'` and `"strings"`), `.equ NAME, VALUE` and user macros
+/// defined between `.macro NAME PARAMS` and `.endm`, where `\PARAM` is
+/// replaced by the argument and `\@` by a unique number, except inside
+/// literals.
+///
+/// The built-in macros `call`, `ret`, `push`, `pop` and `jmp` keep a stack at
+/// the relative base, with `[rb+0]` being the top element. A program using
+/// them should first point `rb` at free memory, e.g. `REL #stack`.
+pub fn assemble(source: &str) -> Result<Vec<Unit>, AssemblerError> {
+    let (lines, macros) = collect_macros(source)?;
+
+    let mut expanded = Vec::new();
+    let mut unique = 0;
+
+    for line in lines {
+        let text = strip_address(&line.text).to_string();
+        expand(Line { number: line.number, text }, &macros, &mut unique, 0, &mut expanded)?;
+    }
+
+    let mut symbols: HashMap<String, Unit> = HashMap::new();
+    let mut items: Vec<(usize, Item)> = Vec::new();
+    let mut address = 0;
+
+    for line in &expanded {
+        let (label, rest) = split_label(&line.text);
+
+        if let Some(label) = label {
+            if symbols.insert(label.to_string(), address as Unit).is_some() {
+                return error(line.number, format!("Duplicate symbol: {}", label));
+            }
+        }
+
+        if let Some(item) = parse_item(rest, line.number, &mut symbols)? {
+            address += item.size();
+            items.push((line.number, item));
+        }
+    }
+
+    let mut memory = Vec::with_capacity(address);
+
+    for (number, item) in items {
+        match item {
+            Item::Instruction(op, operands) => {
+                let operands = operands.iter()
+                    .map(|(mode, expr)| Ok(Operand { mode: *mode, value: expr.resolve(&symbols, number)? }))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                memory.extend(Instruction::from_operands(memory.len(), op, operands).encode());
+            }
+            Item::Data(values) => {
+                for value in values {
+                    memory.push(value.resolve(&symbols, number)?);
+                }
+            }
+        }
+    }
+
+    Ok(memory)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{disassemble, Computer, State};
+
+    #[test]
+    fn test_assemble() {
+        let source = "
+            .equ x, 3
+            start:  IN -> [rb+x]        ; read a value
+                    OUT [rb+x]
+                    JNZ [rb+x] #start
+                    HALT
+            msg:    .data \"hi\\n\", 'x', msg+1
+        ";
+
+        assert_eq!(assemble(source), Ok(vec![203, 3, 204, 3, 1205, 3, 0, 99, 104, 105, 10, 120, 9]));
+    }
+
+    #[test]
+    fn test_call_and_ret() {
+        let source = "
+                    REL #stack
+                    call double
+                    OUT [rb+0]
+                    HALT
+            double: push #21
+                    ADD [rb+0] [rb+0] -> [rb+0]
+                    pop [result]
+                    ret
+            result: .data 0
+            stack:  .data 0
+        ";
+
+        let memory = assemble(source).unwrap();
+        let mut computer = Computer::new(&memory, None);
+
+        assert_eq!(computer.run(), Ok(State::Halted));
+        assert_eq!(computer.get_memory()[memory.len() - 2], 42);
+    }
+
+    #[test]
+    fn test_user_macro() {
+        let source = "
+            .macro countdown n
+            ADD #\\n #0 -> [counter]
+            loop\\@: OUT [counter]
+            ADD [counter] #-1 -> [counter]
+            JNZ [counter] #loop\\@
+            .endm
+            countdown 3
+            HALT
+            counter: .data 0
+        ";
+
+        let mut computer = Computer::new(&assemble(source).unwrap(), None);
+        computer.run().unwrap();
+
+        assert_eq!(computer.get_output().iter().copied().collect::<Vec<Unit>>(), vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn test_macro_substitutes_whole_parameters() {
+        let source = "
+            .macro show a ab n
+            OUT #\\a
+            OUT #\\ab
+            .data \"\\n\", '\\n', \\n
+            .endm
+            show 1 2 3
+        ";
+
+        assert_eq!(assemble(source), Ok(vec![104, 1, 104, 2, 10, 10, 3]));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(assemble("ADD #1 #2 -> #3").unwrap_err().line, 1);
+        assert_eq!(assemble("\nFOO #1").unwrap_err().line, 2);
+        assert_eq!(assemble("JZ #0 #nowhere").unwrap_err().message, "Undefined symbol: nowhere");
+        assert_eq!(assemble("OUT #'").unwrap_err().message, "Unterminated literal: '");
+        assert_eq!(assemble("OUT [']").unwrap_err().message, "Unterminated literal: '");
+        assert_eq!(assemble("OUT #'a").unwrap_err().message, "Unterminated literal: 'a");
+        assert_eq!(assemble("OUT #0\nx: OUT #x+170141183460469231731687303715884105727").unwrap_err().message,
+            "Value out of range: x+170141183460469231731687303715884105727");
+        assert_eq!(assemble("OUT [rb--170141183460469231731687303715884105728]").unwrap_err().message,
+            "Value out of range: -(-170141183460469231731687303715884105728)");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let memory = vec![1005, 9, 7, 204, -3, 99, 42, 1101, 5, 6, 10, 104, 10004, 1, 99];
+        assert_eq!(assemble(&disassemble(&memory).to_string()), Ok(memory));
+    }
+}
@@ -0,0 +1,26 @@
+use std::io::{self, Read};
+use std::{env, fs, process};
+
+use intcode::assemble;
+
+fn main() {
+    let source = match env::args().nth(1) {
+        Some(path) => fs::read_to_string(&path).unwrap_or_else(|_| panic!("Failed to read file: {}", path)),
+        None => {
+            let mut source = String::new();
+            io::stdin().read_to_string(&mut source).expect("Failed to read source");
+            source
+        }
+    };
+
+    match assemble(&source) {
+        Ok(memory) => {
+            let memory: Vec<String> = memory.iter().map(|value| value.to_string()).collect();
+            println!("{}", memory.join(","));
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+}
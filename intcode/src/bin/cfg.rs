@@ -0,0 +1,14 @@
+use intcode::{read_program_and_trace, ControlFlowGraph};
+
+fn main() {
+    let usage = "Usage: cfg PROGRAM [TRACE]";
+    let (memory, trace) = read_program_and_trace(std::env::args().skip(1)).unwrap_or_else(|e| panic!("{}\n{}", e, usage));
+
+    let graph = ControlFlowGraph::with_trace(&memory, &trace);
+
+    for block in graph.unresolved() {
+        eprintln!("Unresolved indirect jump at {}", graph.blocks()[&block].instructions.last().unwrap().address);
+    }
+
+    print!("{}", graph.to_dot());
+}
@@ -0,0 +1,20 @@
+use std::{env, fs, io};
+
+use intcode::{parse_memory, Computer, Debugger, Unit};
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let path = args.next().expect("Usage: debug PROGRAM [INPUT...]");
+
+    let memory = fs::read_to_string(&path).unwrap_or_else(|_| panic!("Failed to read file: {}", path));
+    let memory = parse_memory(&memory).expect("Failed to parse program");
+
+    let input: Vec<Unit> = args
+        .map(|arg| arg.parse().expect("Expected input parameters to be integers"))
+        .collect();
+
+    let mut debugger = Debugger::new(Computer::new(&memory, Some(&input)));
+    let stdin = io::stdin();
+
+    debugger.repl(stdin.lock(), io::stdout()).expect("Failed to run debugger");
+}
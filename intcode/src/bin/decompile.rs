@@ -0,0 +1,8 @@
+use intcode::{decompile, read_program_and_trace, ControlFlowGraph};
+
+fn main() {
+    let usage = "Usage: decompile PROGRAM [TRACE]";
+    let (memory, trace) = read_program_and_trace(std::env::args().skip(1)).unwrap_or_else(|e| panic!("{}\n{}", e, usage));
+
+    print!("{}", decompile(&ControlFlowGraph::with_trace(&memory, &trace)));
+}
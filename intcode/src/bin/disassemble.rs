@@ -0,0 +1,15 @@
+use std::{env, fs};
+
+use intcode::{disassemble, parse_memory, read_memory, Unit};
+
+fn main() {
+    let memory: Vec<Unit> = match env::args().nth(1) {
+        Some(path) => {
+            let memory = fs::read_to_string(&path).unwrap_or_else(|_| panic!("Failed to read file: {}", path));
+            parse_memory(&memory).expect("Failed to parse program")
+        }
+        None => read_memory().expect("Failed to read program"),
+    };
+
+    print!("{}", disassemble(&memory));
+}
@@ -0,0 +1,125 @@
+use std::{env, process};
+
+use intcode::{run_computer, Fuzzer, Generator, Outcome, Termination, Unit};
+
+// Copies of the standalone VMs of day 5 and day 7
+#[allow(dead_code, clippy::all)]
+mod legacy_05b;
+
+#[allow(dead_code, clippy::all)]
+mod legacy_07b;
+
+fn to_i32(values: &[Unit]) -> Vec<i32> {
+    values.iter().map(|value| *value as i32).collect()
+}
+
+fn to_units(values: impl IntoIterator<Item = i32>) -> Vec<Unit> {
+    values.into_iter().map(Unit::from).collect()
+}
+
+fn run_05b(memory: &[Unit], input: &[Unit]) -> Outcome {
+    let mut computer = legacy_05b::Computer::new(&to_i32(memory), &to_i32(input));
+    let memory = computer.run();
+
+    Outcome {
+        output: to_units(computer.get_output().iter().copied()),
+        memory: to_units(memory),
+        termination: Termination::Halted,
+    }
+}
+
+fn run_07b(memory: &[Unit], input: &[Unit]) -> Outcome {
+    let mut computer = legacy_07b::Computer::new(&to_i32(memory), Some(&to_i32(input)));
+
+    let termination = match computer.run() {
+        legacy_07b::State::Halted => Termination::Halted,
+        legacy_07b::State::Blocked => Termination::Blocked,
+        state => Termination::Failed(format!("stopped {:?}", state)),
+    };
+
+    Outcome {
+        output: to_units(computer.get_output().iter().copied()),
+        memory: to_units(computer.get_memory().iter().copied()),
+        termination,
+    }
+}
+
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let usage = "Usage: fuzz [--seed N] [--runs N] [--instructions N] [--max-value N]";
+
+    let mut option = |name: &str| match args.iter().position(|arg| arg == name) {
+        Some(i) if i + 1 < args.len() => Some(args.drain(i..i + 2).nth(1).unwrap().parse::<u64>().expect(usage)),
+        Some(_) => panic!("{}", usage),
+        None => None,
+    };
+
+    let seed = option("--seed").unwrap_or(0);
+    let runs = option("--runs").unwrap_or(10_000);
+    let mut generator = Generator::default();
+
+    if let Some(instructions) = option("--instructions") {
+        generator.instructions = instructions as usize;
+    }
+
+    if let Some(max_value) = option("--max-value") {
+        generator.max_value = max_value as Unit;
+    }
+
+    let mut fuzzer = Fuzzer::new(generator);
+    fuzzer.add("intcode", run_computer);
+    fuzzer.add("05b", run_05b);
+    fuzzer.add("07b", run_07b);
+
+    match fuzzer.run(seed..seed + runs) {
+        Some(divergence) => {
+            print!("{}", divergence);
+            process::exit(1);
+        }
+        None => println!("No divergence in {} programs", runs),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use super::*;
+
+    /// The lines of `source` that are neither blank nor comments, trimmed.
+    fn code(source: &str) -> Vec<&str> {
+        source.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with("//")).collect()
+    }
+
+    /// Checks that `copy`, up to any accessors added for the fuzzer, is the
+    /// start of `mod int_code` in the main.rs of `day`.
+    fn assert_matches_day(copy: &str, day: &str) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("..").join(day).join("src/main.rs");
+        let source = fs::read_to_string(&path).unwrap_or_else(|_| panic!("Failed to read file: {}", path.display()));
+        let source = code(&source);
+
+        let copy = code(copy.split("// Accessors for the fuzzer").next().unwrap());
+        let start = source.iter().position(|line| *line == "mod int_code {").expect("No int_code module") + 1;
+
+        assert_eq!(&source[start..(start + copy.len()).min(source.len())], copy.as_slice(), "{} has drifted from its copy", day);
+    }
+
+    #[test]
+    fn test_no_overflow_divergence() {
+        // Without the i32 bound these values overflow in the first seeds
+        let generator = Generator { max_value: 100_000, ..Generator::default() };
+        let mut fuzzer = Fuzzer::new(generator);
+        fuzzer.add("intcode", run_computer);
+        fuzzer.add("05b", run_05b);
+        fuzzer.add("07b", run_07b);
+
+        assert!(fuzzer.run(0..200).is_none());
+    }
+
+    #[test]
+    fn test_copies_match_days() {
+        assert_matches_day(include_str!("legacy_05b.rs"), "05b");
+        assert_matches_day(include_str!("legacy_07b.rs"), "07b");
+    }
+}
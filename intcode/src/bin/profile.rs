@@ -0,0 +1,51 @@
+use std::{env, fs};
+
+use intcode::{parse_memory, Computer, TraceSink, Unit};
+
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let usage = "Usage: profile [--folded PATH] [--trace PATH] PROGRAM [INPUT...]";
+
+    let mut option = |name: &str| match args.iter().position(|arg| arg == name) {
+        Some(i) if i + 1 < args.len() => Some(args.drain(i..i + 2).nth(1).unwrap()),
+        Some(_) => panic!("{}", usage),
+        None => None,
+    };
+
+    let folded = option("--folded");
+    let trace = option("--trace");
+
+    let mut args = args.into_iter();
+    let path = args.next().expect(usage);
+
+    let memory = fs::read_to_string(&path).unwrap_or_else(|_| panic!("Failed to read file: {}", path));
+    let memory = parse_memory(&memory).expect("Failed to parse program");
+
+    let input: Vec<Unit> = args
+        .map(|arg| arg.parse().expect("Expected input parameters to be integers"))
+        .collect();
+
+    let mut computer = Computer::new(&memory, Some(&input));
+    computer.start_profiling();
+    computer.start_code_tracking();
+
+    if let Some(path) = &trace {
+        computer.set_trace(TraceSink::create(path).unwrap_or_else(|_| panic!("Failed to create file: {}", path)));
+    }
+
+    let state = computer.run().unwrap_or_else(|e| panic!("{}", e));
+    let output: Vec<String> = computer.get_output().iter().map(Unit::to_string).collect();
+    let profile = computer.stop_profiling().unwrap();
+
+    if let Some(trace) = computer.clear_trace() {
+        trace.flush().expect("Failed to write trace");
+    }
+
+    println!("Stopped {:?} with output: {}\n", state, output.join(","));
+    print!("{}", profile.report(computer.get_memory(), 20));
+    print!("\n{}", computer.get_code_tracker().unwrap().summary());
+
+    if let Some(path) = folded {
+        fs::write(&path, profile.folded()).unwrap_or_else(|_| panic!("Failed to write file: {}", path));
+    }
+}
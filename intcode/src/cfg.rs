@@ -0,0 +1,379 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use crate::{label_name, AddressingMode, Instruction, Op, Operand, TraceRecord, Unit, Word};
+
+/// How control leaves a basic block.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Exit {
+    /// Into the block that follows, without a jump.
+    Fallthrough,
+    /// Through a jump with an immediate target, possibly conditional.
+    Jump,
+    /// Through a jump to a function, see `ControlFlowGraph`.
+    Call,
+    /// Through a jump to a relative address, which is how functions return.
+    Return,
+    /// Through a jump to an absolute address whose value is not known
+    /// statically.
+    Indirect,
+    Halt,
+    /// Into a word that does not decode as an instruction.
+    Invalid,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum EdgeKind {
+    Fallthrough,
+    Jump,
+    Call,
+    /// From a call to the instruction after it, where the callee returns to.
+    CallReturn,
+    /// A jump seen in a trace but not known statically.
+    Observed,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: EdgeKind,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Block {
+    pub start: usize,
+    pub instructions: Vec<Instruction>,
+    pub exit: Exit,
+}
+
+impl Block {
+    /// One past the last word of the block.
+    pub fn end(&self) -> usize {
+        self.instructions.last().map_or(self.start, |instruction| instruction.address + instruction.size())
+    }
+}
+
+/// The control-flow graph of an Intcode program, found by following every
+/// path from address 0 and splitting it into basic blocks at jumps and
+/// jump targets.
+///
+/// Compiled Intcode calls a function by storing the return address at a
+/// relative address and jumping to the function, which moves the relative
+/// base with `REL` and returns by jumping to the relative address holding
+/// the return address. A jump with an immediate target that follows such a
+/// store in the same straight line of code is taken to be a call, and a
+/// jump with a relative target to be a return. Other jumps whose target is
+/// not immediate cannot be followed statically, but jumps observed in a
+/// trace can be merged in with `with_trace`.
+#[derive(Debug, Clone)]
+pub struct ControlFlowGraph {
+    blocks: BTreeMap<usize, Block>,
+    edges: BTreeSet<Edge>,
+    functions: BTreeSet<usize>,
+}
+
+/// Whether `instruction` stores the immediate `value` at a relative address.
+fn stores_relative(instruction: &Instruction, value: usize) -> bool {
+    let immediate = |operand: &Operand| operand.mode == AddressingMode::Immediate;
+
+    match (instruction.op, instruction.operands.as_slice()) {
+        (Op::Add, [a, b, dest]) if dest.mode == AddressingMode::Relative && immediate(a) && immediate(b) => {
+            a.value.checked_add(b.value) == Some(value as Unit)
+        }
+        (Op::Mul, [a, b, dest]) if dest.mode == AddressingMode::Relative && immediate(a) && immediate(b) => {
+            a.value.checked_mul(b.value) == Some(value as Unit)
+        }
+        _ => false,
+    }
+}
+
+fn is_jump(instruction: &Instruction) -> bool {
+    matches!(instruction.op, Op::JumpNZ | Op::JumpZ)
+}
+
+/// Jumps taken in a trace, as `(pc, target)` pairs.
+pub fn observed_jumps<'a, W: Word>(records: impl IntoIterator<Item = &'a TraceRecord<W>>) -> BTreeSet<(usize, usize)> {
+    let zero = W::default();
+
+    records.into_iter()
+        .filter(|record| match (record.op, record.values.first()) {
+            (Op::JumpNZ, Some(value)) => *value != zero,
+            (Op::JumpZ, Some(value)) => *value == zero,
+            _ => false,
+        })
+        .filter_map(|record| record.values.get(1)?.to_unit().filter(|target| *target >= 0).map(|target| (record.pc, target as usize)))
+        .collect()
+}
+
+impl ControlFlowGraph {
+    pub fn new(memory: &[Unit]) -> ControlFlowGraph {
+        ControlFlowGraph::with_jumps(memory, &BTreeSet::new())
+    }
+
+    /// Builds the graph, also following the jumps taken in a trace of the
+    /// program.
+    pub fn with_trace<W: Word>(memory: &[Unit], trace: &[TraceRecord<W>]) -> ControlFlowGraph {
+        ControlFlowGraph::with_jumps(memory, &observed_jumps(trace))
+    }
+
+    /// Builds the graph, also following the given `(pc, target)` jumps.
+    pub fn with_jumps(memory: &[Unit], jumps: &BTreeSet<(usize, usize)>) -> ControlFlowGraph {
+        let mut instructions: BTreeMap<usize, Instruction> = BTreeMap::new();
+        let mut leaders: BTreeSet<usize> = std::iter::once(0).chain(jumps.iter().map(|(_, to)| *to)).collect();
+        let mut calls: BTreeSet<usize> = BTreeSet::new();
+        let mut worklist: Vec<usize> = leaders.iter().rev().copied().collect();
+
+        while let Some(mut address) = worklist.pop() {
+            let mut run: Vec<Instruction> = Vec::new();
+
+            while !instructions.contains_key(&address) {
+                let instruction = match Instruction::decode(memory, address) {
+                    Some(instruction) => instruction,
+                    None => break,
+                };
+
+                let next = address + instruction.size();
+
+                if is_jump(&instruction) {
+                    if let Some(target) = instruction.jump_target().filter(|_| instruction.may_jump()) {
+                        leaders.insert(target);
+                        worklist.push(target);
+
+                        if !instruction.falls_through() && run.iter().any(|stored| stores_relative(stored, next)) {
+                            calls.insert(address);
+                            worklist.push(next);
+                        }
+                    }
+
+                    leaders.insert(next);
+                }
+
+                let falls_through = instruction.falls_through();
+                instructions.insert(address, instruction.clone());
+                run.push(instruction);
+
+                if !falls_through {
+                    break;
+                }
+
+                address = next;
+            }
+        }
+
+        leaders.extend(calls.iter().map(|call| instructions[call].address + instructions[call].size()));
+
+        let mut graph = ControlFlowGraph { blocks: BTreeMap::new(), edges: BTreeSet::new(), functions: BTreeSet::new() };
+        let mut current: Option<Block> = None;
+
+        for instruction in instructions.values() {
+            let contiguous = current.as_ref().is_some_and(|block| block.end() == instruction.address);
+
+            if !contiguous || leaders.contains(&instruction.address) {
+                if let Some(block) = current.take() {
+                    graph.blocks.insert(block.start, block);
+                }
+
+                current = Some(Block { start: instruction.address, instructions: Vec::new(), exit: Exit::Fallthrough });
+            }
+
+            let block = current.as_mut().unwrap();
+            block.instructions.push(instruction.clone());
+
+            if is_jump(instruction) || instruction.op == Op::Halt {
+                let block = current.take().unwrap();
+                graph.blocks.insert(block.start, block);
+            }
+        }
+
+        if let Some(block) = current {
+            graph.blocks.insert(block.start, block);
+        }
+
+        for block in graph.blocks.values_mut() {
+            let last = block.instructions.last().unwrap();
+
+            block.exit = match last.op {
+                Op::Halt => Exit::Halt,
+                _ if calls.contains(&last.address) => Exit::Call,
+                Op::JumpNZ | Op::JumpZ => match last.operands[1].mode {
+                    AddressingMode::Immediate => Exit::Jump,
+                    AddressingMode::Relative => Exit::Return,
+                    AddressingMode::Absolute => Exit::Indirect,
+                },
+                _ if instructions.contains_key(&block.end()) => Exit::Fallthrough,
+                _ => Exit::Invalid,
+            };
+
+            let (start, end) = (block.start, block.end());
+            let mut edges = Vec::new();
+
+            if let Some(target) = last.jump_target().filter(|_| last.may_jump()) {
+                match block.exit {
+                    Exit::Call => {
+                        edges.push(Edge { from: start, to: target, kind: EdgeKind::Call });
+                        edges.push(Edge { from: start, to: end, kind: EdgeKind::CallReturn });
+                        graph.functions.insert(target);
+                    }
+                    _ => edges.push(Edge { from: start, to: target, kind: EdgeKind::Jump }),
+                }
+            }
+
+            if last.falls_through() && instructions.contains_key(&end) {
+                edges.push(Edge { from: start, to: end, kind: EdgeKind::Fallthrough });
+            }
+
+            for (_, to) in jumps.range((last.address, 0)..=(last.address, usize::MAX)) {
+                if last.jump_target() != Some(*to) {
+                    edges.push(Edge { from: start, to: *to, kind: EdgeKind::Observed });
+                }
+            }
+
+            graph.edges.extend(edges);
+        }
+
+        graph
+    }
+
+    pub fn blocks(&self) -> &BTreeMap<usize, Block> {
+        &self.blocks
+    }
+
+    pub fn edges(&self) -> &BTreeSet<Edge> {
+        &self.edges
+    }
+
+    /// Addresses called as functions.
+    pub fn functions(&self) -> &BTreeSet<usize> {
+        &self.functions
+    }
+
+    /// Blocks ending in an indirect jump that no trace has resolved.
+    pub fn unresolved(&self) -> Vec<usize> {
+        self.blocks.values()
+            .filter(|block| block.exit == Exit::Indirect)
+            .filter(|block| !self.edges.iter().any(|edge| edge.from == block.start && edge.kind == EdgeKind::Observed))
+            .map(|block| block.start)
+            .collect()
+    }
+
+    /// The graph in Graphviz DOT format. Functions are drawn with a double
+    /// border and unresolved indirect jumps in red.
+    pub fn to_dot(&self) -> String {
+        let unresolved: BTreeSet<usize> = self.unresolved().into_iter().collect();
+        let mut dot = String::from("digraph cfg {\n    node [shape=box, fontname=\"monospace\"];\n");
+
+        for block in self.blocks.values() {
+            let mut label = format!("{}:\\l", label_name(block.start));
+
+            for instruction in &block.instructions {
+                write!(label, "{:>6}  {}\\l", instruction.address, instruction).unwrap();
+            }
+
+            let mut attributes = vec![format!("label=\"{}\"", label)];
+
+            match block.exit {
+                Exit::Return => attributes.push(String::from("xlabel=\"return\"")),
+                Exit::Invalid => attributes.push(String::from("xlabel=\"invalid\", style=dashed")),
+                _ => (),
+            }
+
+            if unresolved.contains(&block.start) {
+                attributes.push(String::from("xlabel=\"unresolved\", color=red"));
+            }
+
+            if self.functions.contains(&block.start) {
+                attributes.push(String::from("peripheries=2"));
+            }
+
+            writeln!(dot, "    {} [{}];", label_name(block.start), attributes.join(", ")).unwrap();
+        }
+
+        for edge in &self.edges {
+            let attributes = match edge.kind {
+                EdgeKind::Fallthrough => "",
+                EdgeKind::Jump => " [label=\"jump\"]",
+                EdgeKind::Call => " [label=\"call\", style=bold]",
+                EdgeKind::CallReturn => " [style=dotted]",
+                EdgeKind::Observed => " [label=\"observed\", style=dashed]",
+            };
+
+            writeln!(dot, "    {} -> {}{};", label_name(edge.from), label_name(edge.to), attributes).unwrap();
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assemble;
+
+    #[test]
+    fn test_control_flow_graph() {
+        let memory = assemble("
+                    REL #100
+                    ADD #back #0 -> [rb+0]
+                    JZ #0 #function
+            back:   JNZ [flag] [target]
+            done:   HALT
+            function:
+                    REL #1
+                    REL #-1
+                    JZ #0 [rb+0]
+            flag:   .data 1
+            target: .data 12
+        ").unwrap();
+
+        let graph = ControlFlowGraph::new(&memory);
+        let exits: Vec<(usize, Exit)> = graph.blocks().values().map(|block| (block.start, block.exit)).collect();
+
+        assert_eq!(exits, vec![(0, Exit::Call), (9, Exit::Indirect), (12, Exit::Halt), (13, Exit::Return)]);
+        assert_eq!(graph.edges().iter().copied().collect::<Vec<Edge>>(), vec![
+            Edge { from: 0, to: 9, kind: EdgeKind::CallReturn },
+            Edge { from: 0, to: 13, kind: EdgeKind::Call },
+            Edge { from: 9, to: 12, kind: EdgeKind::Fallthrough },
+        ]);
+        assert_eq!(graph.functions().iter().copied().collect::<Vec<usize>>(), vec![13]);
+        assert_eq!(graph.unresolved(), vec![9]);
+        assert!(graph.to_dot().contains("    L9 [label=\"L9:\\l     9  JNZ [20] [21]\\l\", xlabel=\"unresolved\", color=red];\n"));
+
+        let jumps = [(9, 12), (17, 9)].iter().copied().collect();
+        let graph = ControlFlowGraph::with_jumps(&memory, &jumps);
+
+        assert!(graph.unresolved().is_empty());
+        assert!(graph.edges().contains(&Edge { from: 13, to: 9, kind: EdgeKind::Observed }));
+        assert!(graph.to_dot().contains("    L9 -> L12 [label=\"observed\", style=dashed];\n"));
+    }
+
+    #[test]
+    fn test_overflowing_stores_are_not_calls() {
+        // ADD and MUL of constants that overflow, stored like a return address
+        for opcode in [21101, 21102].iter() {
+            let memory = vec![*opcode, Unit::MAX, 2, 0, 1106, 0, 8, 99, 99];
+            let graph = ControlFlowGraph::new(&memory);
+
+            assert_eq!(graph.blocks()[&0].exit, Exit::Jump);
+            assert!(graph.functions().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_observed_jumps() {
+        let record = |pc: usize, values: Vec<Unit>| TraceRecord {
+            step: 1,
+            pc,
+            opcode: 1105,
+            op: Op::JumpNZ,
+            modes: vec![AddressingMode::Immediate; values.len()],
+            values,
+            write: None,
+            relative_base: 0,
+        };
+
+        let trace = vec![record(0, vec![1, 7]), record(3, vec![0, 9]), record(6, vec![1]), record(9, vec![])];
+        assert_eq!(observed_jumps(&trace).into_iter().collect::<Vec<(usize, usize)>>(), vec![(0, 7)]);
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::{BTreeSet, HashSet};
+use std::fmt::Write;
+
+use crate::Unit;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum CodeRole {
+    Opcode,
+    Operand,
+}
+
+/// A write by the instruction at `pc` to an address that had already been
+/// executed, as an opcode if it ever was one and as an operand otherwise.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CodeWrite<W = Unit> {
+    pub pc: usize,
+    pub address: usize,
+    pub role: CodeRole,
+    pub old: W,
+    pub new: W,
+}
+
+/// Tracks the addresses executed as instructions while a computer runs with
+/// code tracking enabled, and the writes the program makes to them. Writes
+/// by `Computer::poke` are not the program's and are not recorded.
+///
+/// An instruction counts as executed before it runs, so an instruction that
+/// overwrites its own operands is caught too.
+#[derive(Debug, Clone)]
+pub struct CodeTracker<W = Unit> {
+    opcodes: HashSet<usize>,
+    operands: HashSet<usize>,
+    writes: Vec<CodeWrite<W>>,
+}
+
+impl<W> Default for CodeTracker<W> {
+    fn default() -> CodeTracker<W> {
+        CodeTracker { opcodes: HashSet::new(), operands: HashSet::new(), writes: Vec::new() }
+    }
+}
+
+impl<W: Clone> CodeTracker<W> {
+    pub fn new() -> CodeTracker<W> {
+        CodeTracker::default()
+    }
+
+    pub(crate) fn instruction(&mut self, pc: usize, num_params: usize) {
+        self.opcodes.insert(pc);
+        self.operands.extend(pc + 1..pc + 1 + num_params);
+    }
+
+    pub(crate) fn write(&mut self, pc: usize, address: usize, old: &W, new: &W) {
+        let role = if self.opcodes.contains(&address) {
+            CodeRole::Opcode
+        } else if self.operands.contains(&address) {
+            CodeRole::Operand
+        } else {
+            return;
+        };
+
+        self.writes.push(CodeWrite { pc, address, role, old: old.clone(), new: new.clone() });
+    }
+
+    pub fn is_executed(&self, address: usize) -> bool {
+        self.opcodes.contains(&address) || self.operands.contains(&address)
+    }
+
+    pub fn is_self_modifying(&self) -> bool {
+        !self.writes.is_empty()
+    }
+
+    /// Every write into executed code, in the order they happened.
+    pub fn writes(&self) -> &[CodeWrite<W>] {
+        &self.writes
+    }
+
+    /// A one line verdict, followed by the writing and written addresses if
+    /// the program modified itself.
+    pub fn summary(&self) -> String {
+        let executed = self.opcodes.union(&self.operands).count();
+
+        if !self.is_self_modifying() {
+            return format!("Not self-modifying: no writes to the {} executed addresses\n", executed);
+        }
+
+        let join = |addresses: BTreeSet<usize>| {
+            addresses.iter().map(usize::to_string).collect::<Vec<String>>().join(", ")
+        };
+        let count = |role| self.writes.iter().filter(|write| write.role == role).count();
+
+        let mut summary = format!(
+            "Self-modifying: {} writes to executed code, {} to opcodes and {} to operands\n",
+            self.writes.len(),
+            count(CodeRole::Opcode),
+            count(CodeRole::Operand),
+        );
+
+        writeln!(summary, "Writers: {}", join(self.writes.iter().map(|write| write.pc).collect())).unwrap();
+        writeln!(summary, "Targets: {}", join(self.writes.iter().map(|write| write.address).collect())).unwrap();
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{assemble, CodeRole, CodeWrite, Computer};
+
+    #[test]
+    fn test_self_modifying() {
+        // Computes its jump target in data, which is not code
+        let memory = assemble("
+                ADD #0 #patch -> [target]
+            jump:
+                JZ #0 [target]
+            patch:
+                HALT
+            target: .data 0
+        ").unwrap();
+
+        let mut computer = Computer::new(&memory, None);
+        computer.start_code_tracking();
+        computer.run().unwrap();
+        let tracker = computer.stop_code_tracking().unwrap();
+
+        assert!(!tracker.is_self_modifying());
+        assert!(tracker.summary().starts_with("Not self-modifying"));
+
+        // Turns its own first instruction into a halt and jumps back to it
+        let memory = assemble("
+            start:
+                OUT #1
+                ADD #99 #0 -> [start]
+                JZ #0 #start
+        ").unwrap();
+
+        let mut computer = Computer::new(&memory, None);
+        computer.start_code_tracking();
+        computer.run().unwrap();
+        computer.poke(1, 2).unwrap();
+        let tracker = computer.get_code_tracker().unwrap();
+
+        assert!(tracker.is_executed(8));
+        assert_eq!(tracker.writes(), &[CodeWrite { pc: 2, address: 0, role: CodeRole::Opcode, old: 104, new: 99 }]);
+        assert_eq!(tracker.summary(), "\
+            Self-modifying: 1 writes to executed code, 1 to opcodes and 0 to operands\n\
+            Writers: 2\n\
+            Targets: 0\n");
+    }
+}
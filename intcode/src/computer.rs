@@ -0,0 +1,1206 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::num::ParseIntError;
+use std::ops::Range;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use crate::code::CodeTracker;
+use crate::decoded::{DecodeCache, Decoded};
+use crate::history::{History, UndoRecord};
+use crate::watch::Watchpoints;
+use crate::{
+    Access, Arithmetic, InputDevice, IntcodeError, Memory, MemoryWrite, Op, OutputDevice, Profile, TraceRecord, TraceSink,
+    WatchAction, WatchEvent, Word,
+};
+
+use Op::*;
+use State::*;
+use AddressingMode::{Absolute, Immediate, Relative};
+
+pub type Unit = i128;
+
+const SAVE_HEADER: &str = "intcode-computer";
+const SAVE_VERSION: u32 = 2;
+
+fn debug_enabled() -> bool {
+    std::env::var("INTCODE_TRACE").is_ok()
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum State {
+    NotStarted,
+    Running,
+    Blocked,
+    Halted,
+    /// Stopped by `run_for` or `run_with_budget` before halting or blocking.
+    /// Running it again resumes where it left off.
+    OutOfFuel,
+    /// Stopped by a watchpoint after the instruction that triggered it, see
+    /// `get_watch_event`. Running it again resumes where it left off.
+    Paused,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum AddressingMode {
+    Absolute,
+    Immediate,
+    Relative,
+}
+
+pub fn get_addressing_mode(opcode: Unit, position: usize) -> Option<AddressingMode> {
+    match (opcode / (10 as Unit).pow(position as u32 + 2)) % 10 {
+        0 => Some(Absolute),
+        1 => Some(Immediate),
+        2 => Some(Relative),
+        _ => None
+    }
+}
+
+pub fn parse_memory(memory: &str) -> Result<Vec<Unit>, ParseIntError> {
+    parse_words(memory)
+}
+
+/// Like `parse_memory`, but for any word type.
+pub fn parse_words<W: FromStr>(memory: &str) -> Result<Vec<W>, W::Err> {
+    memory
+        .split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse())
+        .collect()
+}
+
+pub fn read_memory() -> Result<Vec<Unit>, io::Error> {
+    let mut memory = String::new();
+    io::stdin().read_line(&mut memory)?;
+
+    parse_memory(&memory).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+type SharedInput<W> = Arc<Mutex<Box<dyn InputDevice<W> + Send>>>;
+type SharedOutput<W> = Arc<Mutex<Box<dyn OutputDevice<W> + Send>>>;
+
+/// An Intcode computer over words of type `W`, by default `Unit`.
+///
+/// Input is taken from the queue filled by `push_input`, and once that is
+/// empty from the input device, if any. Output goes to the output device if
+/// there is one, and otherwise to the queue drained by `pop_output`. Clones
+/// share their devices.
+#[derive(Clone)]
+pub struct Computer<W = Unit> {
+    memory: Memory<W>,
+    input: VecDeque<W>,
+    output: VecDeque<W>,
+    input_device: Option<SharedInput<W>>,
+    output_device: Option<SharedOutput<W>>,
+    pc: usize,
+    state: State,
+    relative_base: Unit,
+    steps: u64,
+    arithmetic: Arithmetic,
+    trace: Option<TraceSink>,
+    profile: Option<Box<Profile>>,
+    code: Option<Box<CodeTracker<W>>>,
+    history: Option<Box<History<W>>>,
+    last_write: Option<MemoryWrite<W>>,
+    watchpoints: Watchpoints<W>,
+    watch_event: Option<WatchEvent<W>>,
+    decoded: DecodeCache,
+    debug: bool,
+}
+
+impl<W: Word> Computer<W> {
+    pub fn new(memory: &Vec<W>, initial_input: Option<&Vec<W>>) -> Computer<W> {
+        Computer::with_memory(Memory::from(memory.as_slice()), initial_input)
+    }
+
+    /// Creates a computer around a preloaded `Memory`, e.g. one with a
+    /// custom page size or allocation limit.
+    pub fn with_memory(memory: Memory<W>, initial_input: Option<&Vec<W>>) -> Computer<W> {
+        let input = match initial_input {
+            Some(input) => VecDeque::from(input.to_owned()),
+            None => VecDeque::new(),
+        };
+
+        Computer {
+            memory,
+            input,
+            output: VecDeque::new(),
+            input_device: None,
+            output_device: None,
+            pc: 0,
+            state: NotStarted,
+            relative_base: 0,
+            steps: 0,
+            arithmetic: Arithmetic::default(),
+            trace: None,
+            profile: None,
+            code: None,
+            history: None,
+            last_write: None,
+            watchpoints: Watchpoints::default(),
+            watch_event: None,
+            decoded: DecodeCache::default(),
+            debug: debug_enabled(),
+        }
+    }
+
+    pub fn with_devices(
+        memory: &Vec<W>,
+        input: impl InputDevice<W> + Send + 'static,
+        output: impl OutputDevice<W> + Send + 'static,
+    ) -> Computer<W> {
+        let mut computer = Computer::new(memory, None);
+        computer.set_input_device(input);
+        computer.set_output_device(output);
+        computer
+    }
+
+    pub fn set_input_device(&mut self, device: impl InputDevice<W> + Send + 'static) {
+        self.input_device = Some(Arc::new(Mutex::new(Box::new(device))));
+    }
+
+    pub fn set_output_device(&mut self, device: impl OutputDevice<W> + Send + 'static) {
+        self.output_device = Some(Arc::new(Mutex::new(Box::new(device))));
+    }
+
+    pub fn clear_devices(&mut self) {
+        self.input_device = None;
+        self.output_device = None;
+    }
+
+    pub fn print(&mut self, value: char) {
+        self.push_input(W::from_unit(value as Unit));
+    }
+
+    pub fn println(&mut self, value: String) {
+        value.chars().for_each(
+            |c| self.print(c)
+        );
+
+        self.print(10 as char);
+    }
+
+    pub fn push_input(&mut self, value: W) {
+        self.input.push_back(value);
+    }
+
+    pub fn pop_output(&mut self) -> Option<W> {
+        self.output.pop_front()
+    }
+
+    pub fn get_input(&self) -> &VecDeque<W> {
+        &self.input
+    }
+
+    pub fn get_output(&self) -> &VecDeque<W> {
+        &self.output
+    }
+
+    pub fn get_memory(&self) -> &Memory<W> {
+        &self.memory
+    }
+
+    pub fn get_state(&self) -> &State {
+        &self.state
+    }
+
+    pub fn get_pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn get_relative_base(&self) -> Unit {
+        self.relative_base
+    }
+
+    /// The number of instructions executed so far.
+    pub fn get_steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn get_arithmetic(&self) -> Arithmetic {
+        self.arithmetic
+    }
+
+    pub fn set_arithmetic(&mut self, arithmetic: Arithmetic) {
+        self.arithmetic = arithmetic;
+    }
+
+    /// Writes a JSON Lines record for every instruction executed from now on.
+    pub fn set_trace(&mut self, trace: TraceSink) {
+        self.trace = Some(trace);
+    }
+
+    pub fn clear_trace(&mut self) -> Option<TraceSink> {
+        self.trace.take()
+    }
+
+    /// Starts collecting a `Profile`, discarding any collected so far.
+    pub fn start_profiling(&mut self) {
+        self.profile = Some(Box::new(Profile::new()));
+    }
+
+    pub fn get_profile(&self) -> Option<&Profile> {
+        self.profile.as_deref()
+    }
+
+    pub fn stop_profiling(&mut self) -> Option<Profile> {
+        self.profile.take().map(|profile| *profile)
+    }
+
+    /// Starts recording which addresses are executed and which writes land
+    /// on them, discarding anything recorded so far.
+    pub fn start_code_tracking(&mut self) {
+        self.code = Some(Box::new(CodeTracker::new()));
+    }
+
+    pub fn get_code_tracker(&self) -> Option<&CodeTracker<W>> {
+        self.code.as_deref()
+    }
+
+    pub fn stop_code_tracking(&mut self) -> Option<CodeTracker<W>> {
+        self.code.take().map(|code| *code)
+    }
+
+    /// Starts keeping an undo log for `step_back`, discarding any kept so
+    /// far. With a `limit` only the last `limit` steps can be undone.
+    pub fn start_history(&mut self, limit: Option<usize>) {
+        self.history = Some(Box::new(History::new(limit)));
+    }
+
+    pub fn get_history(&self) -> Option<&History<W>> {
+        self.history.as_deref()
+    }
+
+    pub fn stop_history(&mut self) -> Option<History<W>> {
+        self.history.take().map(|history| *history)
+    }
+
+    /// Undoes the last executed instruction, restoring memory, pc, relative
+    /// base, state and step counter. A value the instruction read goes back
+    /// to the front of the input queue, even if it came from the input
+    /// device, and a value it wrote to the output queue is taken back if it
+    /// is still the last one there. Output sent to a device, pokes, the
+    /// profile and the code tracker are not undone. Returns false if there
+    /// is nothing to undo.
+    pub fn step_back(&mut self) -> bool {
+        let record = match self.history.as_mut().and_then(|history| history.pop()) {
+            Some(record) => record,
+            None => return false,
+        };
+
+        if let Some(write) = record.write {
+            self.memory.set(write.address, write.old).expect("Restores an allocated address");
+            self.decoded.invalidate(write.address);
+        }
+
+        if let Some(value) = record.input {
+            self.input.push_front(value);
+        }
+
+        if let Some(value) = record.output {
+            if self.output.back() == Some(&value) {
+                self.output.pop_back();
+            }
+        }
+
+        self.pc = record.pc;
+        self.relative_base = record.relative_base;
+        self.state = record.state;
+        self.steps = record.step - 1;
+        self.watch_event = None;
+        true
+    }
+
+    /// Steps back at least once and until the instruction at `pc` is next
+    /// to execute. Returns false, with the computer at the oldest point in
+    /// the log, if it never was.
+    pub fn run_back_to(&mut self, pc: usize) -> bool {
+        while self.step_back() {
+            if self.pc == pc {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Calls `handler` for every read or write of an address in `addresses`
+    /// by an instruction, and for writes by `poke`. Reads cover parameters
+    /// only, not the fetching of instructions. Returns an id for
+    /// `remove_watchpoint`.
+    pub fn add_watchpoint(
+        &mut self,
+        addresses: Range<usize>,
+        access: Access,
+        handler: impl FnMut(&WatchEvent<W>) -> WatchAction + Send + 'static,
+    ) -> usize {
+        self.watchpoints.add(addresses, access, handler)
+    }
+
+    pub fn remove_watchpoint(&mut self, id: usize) -> bool {
+        self.watchpoints.remove(id)
+    }
+
+    /// The event that paused the computer, until it runs again.
+    pub fn get_watch_event(&self) -> Option<&WatchEvent<W>> {
+        self.watch_event.as_ref()
+    }
+
+    /// Writes that would turn `snapshot`, an earlier clone of `get_memory`,
+    /// into the current memory.
+    pub fn changes_since(&self, snapshot: &Memory<W>) -> Vec<MemoryWrite<W>> {
+        snapshot.diff(&self.memory)
+    }
+
+    pub fn peek(&self, address: usize) -> W {
+        self.memory.get(address)
+    }
+
+    pub fn poke(&mut self, address: usize, value: W) -> Result<(), IntcodeError> {
+        // A poke is not the program modifying itself
+        let code = self.code.take();
+        let result = self.write_absolute(address, value);
+        self.code = code;
+        result
+    }
+
+    /// Writes the complete state of the computer in a versioned text format:
+    /// a header line followed by one `key value` line per field, with lists
+    /// as comma separated values. Memory is written as `;` separated
+    /// `address:values` segments. Devices, the trace sink, watchpoints and
+    /// the undo log are not saved.
+    pub fn save(&self, writer: &mut impl Write) -> io::Result<()> {
+        let join = |values: &mut dyn Iterator<Item = &W>| {
+            values.map(|value| value.to_string()).collect::<Vec<String>>().join(",")
+        };
+
+        writeln!(writer, "{} {}", SAVE_HEADER, SAVE_VERSION)?;
+        writeln!(writer, "state {:?}", self.state)?;
+        writeln!(writer, "pc {}", self.pc)?;
+        writeln!(writer, "relative_base {}", self.relative_base)?;
+        writeln!(writer, "steps {}", self.steps)?;
+        writeln!(writer, "arithmetic {:?}", self.arithmetic)?;
+        writeln!(writer, "input {}", join(&mut self.input.iter()))?;
+        writeln!(writer, "output {}", join(&mut self.output.iter()))?;
+        writeln!(writer, "page_size {}", self.memory.page_size())?;
+        writeln!(writer, "memory_limit {}", self.memory.limit())?;
+        writeln!(writer, "memory_len {}", self.memory.len())?;
+
+        let segments = self.memory.segments().iter()
+            .map(|(address, words)| format!("{}:{}", address, join(&mut words.iter())))
+            .collect::<Vec<String>>();
+        writeln!(writer, "memory {}", segments.join(";"))?;
+        writer.flush()
+    }
+
+    /// Restores a computer written by `save`.
+    pub fn load(reader: impl Read) -> io::Result<Computer<W>> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let mut lines = BufReader::new(reader).lines();
+
+        let version = match lines.next().transpose()? {
+            Some(header) if header == format!("{} 1", SAVE_HEADER) => 1,
+            Some(header) if header == format!("{} {}", SAVE_HEADER, SAVE_VERSION) => SAVE_VERSION,
+            Some(header) => return Err(invalid(format!("Unsupported save format: {}", header))),
+            None => return Err(invalid(String::from("Empty save file"))),
+        };
+
+        let mut fields = HashMap::new();
+
+        for line in lines {
+            let line = line?;
+            let (key, value) = line.split_at(line.find(' ').unwrap_or(line.len()));
+            fields.insert(key.to_string(), value.trim().to_string());
+        }
+
+        let field = |key: &str| fields.get(key).ok_or_else(|| invalid(format!("Missing field: {}", key)));
+        let number = |key: &str| field(key)?.parse::<u64>().map_err(|_| invalid(format!("Invalid field: {}", key)));
+        let signed = |key: &str| field(key)?.parse::<Unit>().map_err(|_| invalid(format!("Invalid field: {}", key)));
+        let list = |key: &str| parse_words(field(key)?).map_err(|_| invalid(format!("Invalid field: {}", key)));
+
+        let state = match field("state")?.as_str() {
+            "NotStarted" => NotStarted,
+            "Running" => Running,
+            "Blocked" => Blocked,
+            "Halted" => Halted,
+            "OutOfFuel" => OutOfFuel,
+            "Paused" => Paused,
+            state => return Err(invalid(format!("Unknown state: {}", state))),
+        };
+
+        let arithmetic = match fields.get("arithmetic").map(String::as_str) {
+            None => Arithmetic::default(),
+            Some("Wrapping") => Arithmetic::Wrapping,
+            Some("Saturating") => Arithmetic::Saturating,
+            Some("Trapping") => Arithmetic::Trapping,
+            Some(arithmetic) => return Err(invalid(format!("Unknown arithmetic: {}", arithmetic))),
+        };
+
+        let memory = match version {
+            1 => Memory::from(list("memory")?.as_slice()),
+            _ => {
+                let page_size = number("page_size")? as usize;
+                let limit = number("memory_limit")? as usize;
+
+                if !page_size.is_power_of_two() || page_size > Memory::MAX_PAGE_SIZE {
+                    return Err(invalid(format!("Invalid page size: {}", page_size)));
+                }
+
+                if page_size > limit {
+                    return Err(invalid(format!("Page size {} exceeds the memory limit {}", page_size, limit)));
+                }
+
+                let mut memory = Memory::new(page_size, limit);
+
+                for segment in field("memory")?.split(';').filter(|segment| !segment.is_empty()) {
+                    let (address, words) = segment.split_at(segment.find(':').unwrap_or(0));
+                    let address = address.parse().map_err(|_| invalid(format!("Invalid memory segment: {}", segment)))?;
+                    let words = parse_words(&words[1..]).map_err(|_| invalid(format!("Invalid memory segment: {}", segment)))?;
+
+                    memory.load(address, &words).map_err(|_| invalid(String::from("Memory exceeds its limit")))?;
+                }
+
+                memory.load(number("memory_len")? as usize, &[])
+                    .map_err(|_| invalid(String::from("Memory exceeds its limit")))?;
+                memory
+            }
+        };
+
+        Ok(Computer {
+            memory,
+            input: VecDeque::from(list("input")?),
+            output: VecDeque::from(list("output")?),
+            input_device: None,
+            output_device: None,
+            pc: number("pc")? as usize,
+            state,
+            relative_base: signed("relative_base")?,
+            steps: number("steps")?,
+            arithmetic,
+            trace: None,
+            profile: None,
+            code: None,
+            history: None,
+            last_write: None,
+            watchpoints: Watchpoints::default(),
+            watch_event: None,
+            decoded: DecodeCache::default(),
+            debug: debug_enabled(),
+        })
+    }
+
+    fn write_absolute(&mut self, address: usize, value: W) -> Result<(), IntcodeError> {
+        let write = if self.trace.is_some() || self.code.is_some() || self.history.is_some() || !self.watchpoints.is_empty() {
+            Some(MemoryWrite { address, old: self.memory.get(address), new: value.clone() })
+        } else {
+            None
+        };
+
+        self.memory.set(address, value).map_err(|_| IntcodeError::MemoryLimitExceeded {
+            pc: self.pc,
+            opcode: self.memory[self.pc].to_unit_saturating(),
+            address: address as Unit,
+        })?;
+
+        self.decoded.invalidate(address);
+
+        if let Some(profile) = &mut self.profile {
+            profile.write(address);
+        }
+
+        if let Some(write) = &write {
+            if let Some(code) = &mut self.code {
+                code.write(self.pc, address, &write.old, &write.new);
+            }
+
+            self.watch(Access::Write, address, &write.old, &write.new);
+        }
+
+        if self.trace.is_some() || self.history.is_some() {
+            self.last_write = write;
+        }
+
+        Ok(())
+    }
+
+    fn read_absolute(&self, address: usize) -> W {
+        self.memory.get(address)
+    }
+
+    /// Reads a parameter from memory, as opposed to fetching an instruction.
+    fn read_data(&mut self, address: usize) -> W {
+        if let Some(profile) = &mut self.profile {
+            profile.read(address);
+        }
+
+        let value = self.read_absolute(address);
+
+        if !self.watchpoints.is_empty() {
+            self.watch(Access::Read, address, &value, &value);
+        }
+
+        value
+    }
+
+    fn watch(&mut self, access: Access, address: usize, old: &W, new: &W) {
+        let event = self.watchpoints.check(self.pc, access, address, old, new);
+
+        if self.watch_event.is_none() {
+            self.watch_event = event;
+        }
+    }
+
+    fn read_immediate(&self, offset: usize, opcode: Unit) -> Result<W, IntcodeError> {
+        match self.pc.checked_add(offset) {
+            Some(address) => Ok(self.read_absolute(address)),
+            None => Err(IntcodeError::MemoryLimitExceeded { pc: self.pc, opcode, address: self.pc as Unit + offset as Unit }),
+        }
+    }
+
+    fn to_address(&self, address: Unit, opcode: Unit) -> Result<usize, IntcodeError> {
+        if address < 0 {
+            return Err(IntcodeError::NegativeAddress { pc: self.pc, opcode, address });
+        }
+
+        if address >= usize::MAX as Unit {
+            return Err(IntcodeError::MemoryLimitExceeded { pc: self.pc, opcode, address });
+        }
+
+        Ok(address as usize)
+    }
+
+    fn read_relative(&mut self, offset: Unit, opcode: Unit) -> Result<W, IntcodeError> {
+        let address = self.to_address(self.relative_base.saturating_add(offset), opcode)?;
+        Ok(self.read_data(address))
+    }
+
+    fn debug(&self, args: fmt::Arguments) {
+        if self.debug {
+            eprint!("{}", args);
+        }
+    }
+
+    fn decode(&mut self) -> Result<Decoded, IntcodeError> {
+        if let Some(decoded) = self.decoded.get(self.pc) {
+            return Ok(decoded);
+        }
+
+        let opcode = self.memory[self.pc].to_unit_saturating();
+        let decoded = Decoded::new(opcode)
+            .ok_or(IntcodeError::UnknownOpcode { pc: self.pc, opcode })?;
+
+        self.decoded.insert(self.pc, decoded);
+        Ok(decoded)
+    }
+
+    fn get_mode(&self, position: usize, decoded: &Decoded) -> Result<AddressingMode, IntcodeError> {
+        decoded.modes[position]
+            .ok_or(IntcodeError::UnknownAddressingMode { pc: self.pc, opcode: decoded.opcode, position })
+    }
+
+    fn get_param(&mut self, position: usize, decoded: &Decoded) -> Result<W, IntcodeError> {
+        let value_or_ref = self.read_immediate(position + 1, decoded.opcode)?;
+
+        match self.get_mode(position, decoded)? {
+            Absolute => {
+                self.debug(format_args!(" [{}]", value_or_ref));
+                let address = self.to_address(value_or_ref.to_unit_saturating(), decoded.opcode)?;
+                Ok(self.read_data(address))
+            }
+            Immediate => {
+                self.debug(format_args!(" {}", value_or_ref));
+                Ok(value_or_ref)
+            }
+            Relative => {
+                self.debug(format_args!(" [{}+{}]", self.relative_base, value_or_ref));
+                self.read_relative(value_or_ref.to_unit_saturating(), decoded.opcode)
+            }
+        }
+    }
+
+    fn get_dest(&self, position: usize, decoded: &Decoded) -> Result<usize, IntcodeError> {
+        let value_or_ref = self.read_immediate(position + 1, decoded.opcode)?;
+
+        let address = match self.get_mode(position, decoded)? {
+            Absolute => {
+                self.debug(format_args!(" -> [{}]", value_or_ref));
+                value_or_ref.to_unit_saturating()
+            }
+            Relative => {
+                self.debug(format_args!(" -> [{}+{}]", self.relative_base, value_or_ref));
+                self.relative_base.saturating_add(value_or_ref.to_unit_saturating())
+            }
+            Immediate => {
+                return Err(IntcodeError::ImmediateDestination { pc: self.pc, opcode: decoded.opcode, position });
+            }
+        };
+
+        self.to_address(address, decoded.opcode)
+    }
+
+    /// Resolves the parameters of an instruction. The destination, if any,
+    /// is returned separately as an address.
+    fn get_params(&mut self, decoded: &Decoded) -> Result<([W; 3], usize), IntcodeError> {
+        let (num_params, has_dest) = decoded.op.num_params();
+        let num_input_params = if has_dest { num_params - 1 } else { num_params };
+        let mut params = <[W; 3]>::default();
+        let mut dest = 0;
+
+        for (position, param) in params.iter_mut().enumerate().take(num_input_params) {
+            *param = self.get_param(position, decoded)?;
+        }
+
+        if has_dest {
+            dest = self.get_dest(num_params - 1, decoded)?;
+        }
+
+        Ok((params, dest))
+    }
+
+    fn jump(&mut self, condition: bool, target: &W, opcode: Unit) -> Result<(), IntcodeError> {
+        if condition {
+            self.pc = self.to_address(target.to_unit_saturating(), opcode)?;
+        }
+
+        Ok(())
+    }
+
+    fn arithmetic(&self, decoded: &Decoded, left: &W, right: &W) -> Result<W, IntcodeError> {
+        let result = match (decoded.op, self.arithmetic) {
+            (Add, Arithmetic::Wrapping) => Some(left.wrapping_add(right)),
+            (Add, Arithmetic::Saturating) => Some(left.saturating_add(right)),
+            (Add, Arithmetic::Trapping) => left.checked_add(right),
+            (Mul, Arithmetic::Wrapping) => Some(left.wrapping_mul(right)),
+            (Mul, Arithmetic::Saturating) => Some(left.saturating_mul(right)),
+            (Mul, Arithmetic::Trapping) => left.checked_mul(right),
+            (op, _) => unreachable!("{:?} is not arithmetic", op),
+        };
+
+        result.ok_or_else(|| IntcodeError::Overflow {
+            pc: self.pc,
+            opcode: decoded.opcode,
+            left: left.to_unit_saturating(),
+            right: right.to_unit_saturating(),
+        })
+    }
+
+    fn device_error(&self, decoded: &Decoded, e: io::Error) -> IntcodeError {
+        IntcodeError::Device { pc: self.pc, opcode: decoded.opcode, message: e.to_string() }
+    }
+
+    /// Moves one value from the input device to the input queue. Returns
+    /// false if there is no device or it has nothing to give.
+    fn read_device(&mut self, decoded: &Decoded) -> Result<bool, IntcodeError> {
+        let value = match &self.input_device {
+            Some(device) => device.lock().unwrap().read().map_err(|e| self.device_error(decoded, e))?,
+            None => None,
+        };
+
+        match value {
+            Some(value) => {
+                self.input.push_back(value);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn write_output(&mut self, decoded: &Decoded, value: W) -> Result<(), IntcodeError> {
+        match &self.output_device {
+            Some(device) => device.lock().unwrap().write(value).map_err(|e| self.device_error(decoded, e)),
+            None => {
+                self.output.push_back(value);
+                Ok(())
+            }
+        }
+    }
+
+    fn flag(condition: bool) -> W {
+        W::from_unit(if condition { 1 } else { 0 })
+    }
+
+    fn execute_instruction(&mut self, decoded: &Decoded) -> Result<[W; 3], IntcodeError> {
+        let (mut params, dest) = self.get_params(decoded)?;
+        let zero = W::default();
+
+        match decoded.op {
+            Add | Mul => {
+                let result = self.arithmetic(decoded, &params[0], &params[1])?;
+                self.write_absolute(dest, result)?;
+            }
+            Input => {
+                let value = self.input.pop_front().unwrap();
+                self.write_absolute(dest, value)?;
+            }
+            Output => { self.write_output(decoded, params[0].clone())?; }
+            JumpNZ => { self.jump(params[0] != zero, &params[1], decoded.opcode)?; }
+            JumpZ => { self.jump(params[0] == zero, &params[1], decoded.opcode)?; }
+            LessThan => { self.write_absolute(dest, Computer::flag(params[0] < params[1]))?; }
+            Equals => { self.write_absolute(dest, Computer::flag(params[0] == params[1]))?; }
+            ModRel => {
+                self.relative_base = self.relative_base.saturating_add(params[0].to_unit_saturating());
+            }
+            Halt => unreachable!("Halt is handled by step")
+        }
+
+        let (num_params, has_dest) = decoded.op.num_params();
+
+        if has_dest {
+            params[num_params - 1] = W::from_unit(dest as Unit);
+        }
+
+        Ok(params)
+    }
+
+    /// Executes a single instruction. Returns `Running` if the computer can
+    /// continue, and `Blocked` or `Halted` without executing anything if it
+    /// is waiting for input or has reached a halt instruction.
+    pub fn step(&mut self) -> Result<State, IntcodeError> {
+        if let Halted = self.state {
+            let opcode = self.memory[self.pc].to_unit_saturating();
+            return Err(IntcodeError::HaltedComputer { pc: self.pc, opcode });
+        }
+
+        let old_state = self.state;
+        self.state = Running;
+        self.watch_event = None;
+
+        let decoded = self.decode()?;
+        let op = decoded.op;
+
+        let size = op.num_params().0 + 1;
+        let next_pc = self.pc.checked_add(size).ok_or(IntcodeError::MemoryLimitExceeded {
+            pc: self.pc,
+            opcode: decoded.opcode,
+            address: self.pc as Unit + size as Unit,
+        })?;
+
+        let old_pc = self.pc;
+        let relative_base = self.relative_base;
+        self.last_write = None;
+
+        if self.debug {
+            eprint!("[{:5}] {:>8}", self.pc, format!("{:?}", op));
+        }
+
+        if op == Input && self.input.is_empty() && !self.read_device(&decoded)? {
+            self.debug(format_args!("\n"));
+            self.state = Blocked;
+            return Ok(self.state);
+        }
+
+        if let Some(code) = &mut self.code {
+            code.instruction(old_pc, op.num_params().0);
+        }
+
+        let values = match op {
+            Halt => <[W; 3]>::default(),
+            _ => self.execute_instruction(&decoded)?
+        };
+
+        self.steps += 1;
+        let write = self.last_write.take();
+
+        if let Some(history) = &mut self.history {
+            let queued = op == Output && self.output_device.is_none();
+
+            history.push(UndoRecord {
+                step: self.steps,
+                pc: old_pc,
+                relative_base,
+                state: old_state,
+                write: write.clone(),
+                input: if op == Input { write.as_ref().map(|write| write.new.clone()) } else { None },
+                output: if queued { Some(values[0].clone()) } else { None },
+            });
+        }
+
+        if let Some(trace) = &self.trace {
+            let (num_params, _) = op.num_params();
+
+            trace.record(&TraceRecord {
+                step: self.steps,
+                pc: old_pc,
+                opcode: decoded.opcode,
+                op,
+                modes: decoded.modes.iter().take(num_params).filter_map(|mode| *mode).collect(),
+                values: values[..num_params].to_vec(),
+                write,
+                relative_base,
+            });
+        }
+
+        let jumped = self.pc != old_pc;
+
+        if self.pc == old_pc && op != Halt {
+            self.pc = next_pc;
+        }
+
+        if let Some(profile) = &mut self.profile {
+            let (num_params, _) = op.num_params();
+            let jump = if jumped { Some(self.pc) } else { None };
+            profile.instruction(old_pc, op, &decoded.modes[..num_params], jump, (relative_base, self.relative_base));
+        }
+
+        if let Halt = op {
+            self.debug(format_args!("\n"));
+            self.state = Halted;
+            return Ok(self.state);
+        }
+
+        self.debug(format_args!("\n"));
+
+        if self.watch_event.is_some() {
+            self.state = Paused;
+        }
+
+        Ok(self.state)
+    }
+
+    pub fn run(&mut self) -> Result<State, IntcodeError> {
+        loop {
+            match self.step()? {
+                Running => continue,
+                state => return Ok(state),
+            }
+        }
+    }
+
+    /// Runs at most `steps` instructions, returning `OutOfFuel` if the
+    /// computer neither halted nor blocked in that many.
+    pub fn run_for(&mut self, steps: u64) -> Result<State, IntcodeError> {
+        self.run_with_budget(self.steps.saturating_add(steps))
+    }
+
+    /// Runs until the step counter reaches `budget`, so that a harness can
+    /// bound the total number of instructions across several runs.
+    ///
+    /// A computer that is running or has not started is `OutOfFuel` when the
+    /// budget runs out, even if it ran no instruction. A blocked, paused or
+    /// halted one keeps its state.
+    pub fn run_with_budget(&mut self, budget: u64) -> Result<State, IntcodeError> {
+        while self.steps < budget {
+            match self.step()? {
+                Running => continue,
+                state => return Ok(state),
+            }
+        }
+
+        if let Running | NotStarted = self.state {
+            self.state = OutOfFuel;
+        }
+
+        Ok(self.state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_bigint::BigInt;
+
+    fn run(memory: Vec<Unit>) -> Result<State, IntcodeError> {
+        Computer::new(&memory, None).run()
+    }
+
+    #[test]
+    fn test_unknown_opcode() {
+        assert_eq!(run(vec![1101, 1, 1, 5, 42, 0]), Err(IntcodeError::UnknownOpcode { pc: 4, opcode: 42 }));
+    }
+
+    #[test]
+    fn test_unknown_addressing_mode() {
+        assert_eq!(run(vec![301, 0, 0, 0, 99]), Err(IntcodeError::UnknownAddressingMode { pc: 0, opcode: 301, position: 0 }));
+    }
+
+    #[test]
+    fn test_negative_address() {
+        assert_eq!(run(vec![204, -1, 99]), Err(IntcodeError::NegativeAddress { pc: 0, opcode: 204, address: -1 }));
+        assert_eq!(run(vec![1, -5, 0, 0, 99]), Err(IntcodeError::NegativeAddress { pc: 0, opcode: 1, address: -5 }));
+        assert_eq!(run(vec![109, -5, 204, 0, 99]), Err(IntcodeError::NegativeAddress { pc: 2, opcode: 204, address: -5 }));
+    }
+
+    #[test]
+    fn test_negative_relative_base() {
+        // The base goes below zero, and back up again
+        let mut computer = Computer::new(&vec![109, -5, 109, 12, 204, 0, 99], None);
+        assert_eq!(computer.run_for(1), Ok(OutOfFuel));
+        assert_eq!(computer.get_relative_base(), -5);
+        assert_eq!(computer.run(), Ok(Halted));
+        assert_eq!(computer.get_relative_base(), 7);
+        assert_eq!(computer.pop_output(), Some(0));
+
+        let mut computer = Computer::new(&vec![109, -5, 21101, 1, 2, 15, 204, 15, 99], None);
+        assert_eq!(computer.run(), Ok(Halted));
+        assert_eq!(computer.pop_output(), Some(3));
+        assert_eq!(computer.peek(10), 3);
+    }
+
+    #[test]
+    fn test_immediate_destination() {
+        assert_eq!(run(vec![11101, 1, 1, 0, 99]), Err(IntcodeError::ImmediateDestination { pc: 0, opcode: 11101, position: 2 }));
+    }
+
+    #[test]
+    fn test_halted_computer() {
+        let mut computer = Computer::new(&vec![99], None);
+        assert_eq!(computer.run(), Ok(Halted));
+        assert_eq!(computer.run(), Err(IntcodeError::HaltedComputer { pc: 0, opcode: 99 }));
+    }
+
+    #[test]
+    fn test_run_for() {
+        // Counts down from 3 and halts
+        let mut computer = Computer::new(&vec![1001, 8, -1, 8, 1005, 8, 0, 99, 3], None);
+        assert_eq!(computer.run_for(4), Ok(OutOfFuel));
+        assert_eq!((computer.get_steps(), computer.get_pc()), (4, 0));
+        assert_eq!(computer.run_with_budget(4), Ok(OutOfFuel));
+        assert_eq!(computer.run_for(3), Ok(Halted));
+        assert_eq!(computer.get_steps(), 7);
+        assert_eq!(computer.run_for(0), Ok(Halted));
+        assert_eq!(Computer::new(&vec![1106, 0, 3, 1105, 1, 0], None).run_for(1000), Ok(OutOfFuel));
+
+        let mut computer = Computer::new(&vec![99], None);
+        assert_eq!(computer.run_for(0), Ok(OutOfFuel));
+        assert_eq!(computer.get_steps(), 0);
+        assert_eq!(computer.run_for(1), Ok(Halted));
+    }
+
+    #[test]
+    fn test_run_for_keeps_state() {
+        let mut computer = Computer::new(&vec![3, 0, 99], None);
+        assert_eq!(computer.run(), Ok(Blocked));
+        assert_eq!(computer.run_for(0), Ok(Blocked));
+        assert_eq!(*computer.get_state(), Blocked);
+
+        let mut computer = Computer::new(&vec![1101, 1, 2, 7, 1101, 3, 4, 7, 99], None);
+        computer.add_watchpoint(7..8, Access::Write, |_| WatchAction::Pause);
+        assert_eq!(computer.run_for(1), Ok(Paused));
+        assert_eq!(computer.run_with_budget(1), Ok(Paused));
+        assert_eq!(*computer.get_state(), Paused);
+        assert_eq!(computer.get_watch_event().map(|event| event.new), Some(3));
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        // Counts down from 2 and halts
+        let mut computer = Computer::new(&vec![1001, 8, -1, 8, 1005, 8, 0, 99, 2], None);
+        let reads = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&reads);
+
+        computer.add_watchpoint(8..9, Access::Read, move |event| {
+            log.lock().unwrap().push(event.pc);
+            WatchAction::Continue
+        });
+        let pause = computer.add_watchpoint(0..10, Access::Write, |_| WatchAction::Pause);
+        let snapshot = computer.get_memory().clone();
+
+        assert_eq!(computer.run(), Ok(Paused));
+        assert_eq!(computer.get_pc(), 4);
+        assert_eq!(computer.get_watch_event(), Some(&WatchEvent {
+            watchpoint: pause,
+            pc: 0,
+            access: Access::Write,
+            address: 8,
+            old: 2,
+            new: 1,
+        }));
+        assert_eq!(computer.changes_since(&snapshot), vec![MemoryWrite { address: 8, old: 2, new: 1 }]);
+
+        assert!(computer.remove_watchpoint(pause));
+        assert_eq!(computer.run(), Ok(Halted));
+        assert_eq!(computer.get_watch_event(), None);
+        assert_eq!(*reads.lock().unwrap(), vec![0, 4, 0, 4]);
+    }
+
+    #[test]
+    fn test_step_back() {
+        let memory = vec![3, 9, 1001, 9, 1, 9, 4, 9, 99, 0];
+        let mut computer = Computer::new(&memory, Some(&vec![5]));
+        computer.start_history(None);
+
+        assert_eq!(computer.run(), Ok(Halted));
+        assert_eq!(computer.get_history().unwrap().last_write(9).map(|record| record.pc), Some(2));
+        assert!(computer.run_back_to(2));
+        assert_eq!((computer.peek(9), computer.get_steps(), computer.get_state()), (5, 1, &Running));
+        assert!(computer.get_output().is_empty());
+
+        assert!(computer.step_back());
+        assert!(!computer.step_back());
+        assert_eq!((computer.get_pc(), computer.peek(9), computer.get_state()), (0, 0, &NotStarted));
+        assert_eq!(computer.get_input(), &VecDeque::from(vec![5]));
+        assert_eq!(computer.run(), Ok(Halted));
+        assert_eq!(computer.pop_output(), Some(6));
+
+        let mut computer = Computer::new(&memory, Some(&vec![5]));
+        computer.start_history(Some(2));
+        computer.run().unwrap();
+
+        assert!(!computer.run_back_to(0));
+        assert_eq!((computer.get_pc(), computer.peek(9)), (6, 6));
+    }
+
+    #[test]
+    fn test_self_modifying_code() {
+        let mut computer = Computer::new(&vec![104, 1, 1101, 99, 0, 0, 1105, 1, 0], None);
+        assert_eq!(computer.run(), Ok(Halted));
+        assert_eq!(computer.get_output(), &VecDeque::from(vec![1]));
+        assert_eq!(computer.get_pc(), 0);
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        let far: Vec<Unit> = vec![1101, 1, 2, 1_000_000_000_000, 4, 1_000_000_000_000, 99];
+        let mut computer = Computer::new(&far, None);
+        assert_eq!(computer.run(), Ok(Halted));
+        assert_eq!(computer.get_output().back(), Some(&3));
+
+        let mut computer: Computer = Computer::with_memory(Memory::new(16, 16), None);
+        computer.poke(0, 1101).unwrap();
+        computer.poke(1, 1).unwrap();
+        computer.poke(3, 100).unwrap();
+        assert_eq!(computer.run(), Err(IntcodeError::MemoryLimitExceeded { pc: 0, opcode: 1101, address: 100 }));
+    }
+
+    #[test]
+    fn test_address_below_negative_base() {
+        let mut computer = Computer::new(&vec![109, -5, 204, 10, 99, 42], None);
+        assert_eq!(computer.run(), Ok(Halted));
+        assert_eq!(computer.pop_output(), Some(42));
+
+        // The input lands on the operand of the OUT, which then reads [7]
+        let mut computer = Computer::new(&vec![109, -5, 203, 10, 4, 5, 99], Some(&vec![7]));
+        assert_eq!(computer.run(), Ok(Halted));
+        assert_eq!(computer.peek(5), 7);
+        assert_eq!(computer.pop_output(), Some(0));
+    }
+
+    fn power_of_two<W: Word>(exponent: Unit) -> Option<W> {
+        // Doubles [20] while counting down [21], then outputs [20]
+        let mut memory: Vec<W> = vec![1002, 20, 2, 20, 1001, 21, -1, 21, 1005, 21, 0, 4, 20, 99]
+            .into_iter()
+            .map(W::from_unit)
+            .collect();
+        memory.resize(20, W::default());
+        memory.extend(vec![W::from_unit(1), W::from_unit(exponent)]);
+
+        let mut computer = Computer::new(&memory, None);
+        assert_eq!(computer.run(), Ok(Halted));
+        computer.pop_output()
+    }
+
+    #[test]
+    fn test_word_types() {
+        assert_eq!(power_of_two::<i64>(62), Some(1 << 62));
+        assert_eq!(power_of_two::<i128>(126), Some(1 << 126));
+        assert_eq!(power_of_two::<BigInt>(200), Some(BigInt::from(1) << 200));
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let memory: Vec<i64> = vec![1, 9, 10, 11, 2, 9, 10, 12, 99, i64::MAX - 1, 2, 0, 0];
+
+        // Trapping is the default
+        let mut computer = Computer::new(&memory, None);
+        assert_eq!(computer.get_arithmetic(), Arithmetic::Trapping);
+        assert_eq!(computer.run(), Err(IntcodeError::Overflow { pc: 0, opcode: 1, left: i64::MAX as Unit - 1, right: 2 }));
+
+        let mut computer = Computer::new(&memory, None);
+        computer.set_arithmetic(Arithmetic::Wrapping);
+        assert_eq!(computer.run(), Ok(Halted));
+        assert_eq!((computer.peek(11), computer.peek(12)), (i64::MIN, -4));
+
+        let mut computer = Computer::new(&memory, None);
+        computer.set_arithmetic(Arithmetic::Saturating);
+        assert_eq!(computer.run(), Ok(Halted));
+        assert_eq!((computer.peek(11), computer.peek(12)), (i64::MAX, i64::MAX));
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        // Echoes input values until it reads a 0
+        let memory = vec![109, 20, 203, 0, 1205, 0, 9, 99, 0, 204, 0, 1105, 1, 2];
+        let mut computer = Computer::new(&memory, Some(&vec![7]));
+        computer.push_input(8);
+        assert_eq!(computer.run(), Ok(Blocked));
+        computer.push_input(9);
+
+        let mut saved = Vec::new();
+        computer.save(&mut saved).unwrap();
+        let mut restored = Computer::load(saved.as_slice()).unwrap();
+
+        assert_eq!(restored.get_pc(), computer.get_pc());
+        assert_eq!(restored.get_relative_base(), 20);
+        assert_eq!(restored.get_steps(), computer.get_steps());
+        assert_eq!(restored.get_memory(), computer.get_memory());
+
+        for c in [&mut computer, &mut restored].iter_mut() {
+            c.push_input(0);
+            assert_eq!(c.run(), Ok(Halted));
+        }
+
+        assert_eq!(restored.get_output(), computer.get_output());
+        assert_eq!(restored.get_output().iter().copied().collect::<Vec<Unit>>(), vec![7, 8, 9]);
+    }
+
+    #[test]
+    fn test_load_rejects_unknown_version() {
+        let error = Computer::<Unit>::load("intcode-computer 99\n".as_bytes()).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_top_page() {
+        let memory = vec![1101, 1, 0, usize::MAX as Unit - 1, 99];
+        let mut computer = Computer::new(&memory, None);
+        let snapshot = computer.get_memory().clone();
+        assert_eq!(computer.run(), Ok(Halted));
+
+        assert_eq!(computer.changes_since(&snapshot), vec![MemoryWrite { address: usize::MAX - 1, old: 0, new: 1 }]);
+        assert!(crate::disassemble_memory(computer.get_memory()).to_string().contains(&format!("{}", usize::MAX - 1)));
+
+        let mut saved = Vec::new();
+        computer.save(&mut saved).unwrap();
+        assert_eq!(Computer::<Unit>::load(saved.as_slice()).unwrap().peek(usize::MAX - 1), 1);
+    }
+
+    #[test]
+    fn test_instruction_past_the_end_of_memory() {
+        // Writes an ADD to the last usable address and jumps to it
+        let memory = vec![1101, 1, 0, usize::MAX as Unit - 1, 1105, 1, usize::MAX as Unit - 1];
+        let mut computer = Computer::new(&memory, None);
+
+        assert_eq!(computer.run(), Err(IntcodeError::MemoryLimitExceeded {
+            pc: usize::MAX - 1,
+            opcode: 1,
+            address: usize::MAX as Unit + 3,
+        }));
+    }
+
+    fn load_edited(field: &str, value: &str) -> io::Result<Computer<Unit>> {
+        let mut saved = Vec::new();
+        Computer::new(&vec![1101, 1, 2, 0, 99], None).save(&mut saved).unwrap();
+
+        let saved = String::from_utf8(saved).unwrap().lines()
+            .map(|line| if line.split(' ').next() == Some(field) { format!("{} {}", field, value) } else { line.to_string() })
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        Computer::load(saved.as_bytes())
+    }
+
+    #[test]
+    fn test_load_rejects_malformed_memory() {
+        assert!(load_edited("page_size", "1024").is_ok());
+        assert_eq!(load_edited("page_size", "3").err().unwrap().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(load_edited("page_size", "0").err().unwrap().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(load_edited("page_size", "1099511627776").err().unwrap().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(load_edited("memory_limit", "512").err().unwrap().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(load_edited("memory", "18446744073709551615:1,2").err().unwrap().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(load_edited("memory", "18446744073709551615:1").err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+}
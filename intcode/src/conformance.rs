@@ -0,0 +1,294 @@
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::{Computer, State, Termination, Unit, Word};
+
+/// What a VM needs beyond the opcodes of day 5 to run a case.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Feature {
+    /// Relative mode and REL, from day 9.
+    Relative,
+    /// Addresses beyond the end of the program, from day 9.
+    ExtendedMemory,
+    /// Values that do not fit in 32 bits.
+    LargeNumbers,
+    /// Returning when input runs out, and resuming once there is more.
+    Blocking,
+}
+
+/// The interface the conformance suite drives a VM through. Implement it
+/// for the `Computer` of a day to run `conformance` against it.
+pub trait Vm {
+    fn load(program: &[Unit]) -> Self;
+
+    fn supports(feature: Feature) -> bool;
+
+    fn push_input(&mut self, value: Unit);
+
+    /// Runs until the VM halts, or blocks on input.
+    fn run(&mut self) -> Termination;
+
+    /// Removes and returns everything output so far.
+    fn take_output(&mut self) -> Vec<Unit>;
+
+    fn peek(&self, address: usize) -> Unit;
+}
+
+impl<W: Word> Vm for Computer<W> {
+    fn load(program: &[Unit]) -> Computer<W> {
+        Computer::new(&program.iter().map(|value| W::from_unit(*value)).collect(), None)
+    }
+
+    fn supports(_: Feature) -> bool {
+        true
+    }
+
+    fn push_input(&mut self, value: Unit) {
+        Computer::push_input(self, W::from_unit(value));
+    }
+
+    fn run(&mut self) -> Termination {
+        match Computer::run(self) {
+            Ok(State::Halted) => Termination::Halted,
+            Ok(State::Blocked) => Termination::Blocked,
+            Ok(state) => Termination::Failed(format!("stopped {:?}", state)),
+            Err(e) => Termination::Failed(e.to_string()),
+        }
+    }
+
+    fn take_output(&mut self) -> Vec<Unit> {
+        std::iter::from_fn(|| self.pop_output()).map(|value| value.to_unit_saturating()).collect()
+    }
+
+    fn peek(&self, address: usize) -> Unit {
+        Computer::peek(self, address).to_unit_saturating()
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Check {
+    Input(Vec<Unit>),
+    Run(Termination),
+    /// Any `Termination::Failed`, as VMs word their errors differently.
+    Fail,
+    Output(Vec<Unit>),
+    Memory(usize, Vec<Unit>),
+}
+
+/// A program and the checks to make while running it, in order.
+#[derive(Debug, Clone)]
+struct Case {
+    name: &'static str,
+    program: Vec<Unit>,
+    requires: Vec<Feature>,
+    checks: Vec<Check>,
+}
+
+impl Case {
+    fn new(name: &'static str, program: &[Unit]) -> Case {
+        Case { name, program: program.to_vec(), requires: Vec::new(), checks: Vec::new() }
+    }
+
+    fn requires(mut self, feature: Feature) -> Case {
+        self.requires.push(feature);
+        self
+    }
+
+    fn input(mut self, values: &[Unit]) -> Case {
+        self.checks.push(Check::Input(values.to_vec()));
+        self
+    }
+
+    fn halts(mut self) -> Case {
+        self.checks.push(Check::Run(Termination::Halted));
+        self
+    }
+
+    fn blocks(mut self) -> Case {
+        self.checks.push(Check::Run(Termination::Blocked));
+        self
+    }
+
+    fn fails(mut self) -> Case {
+        self.checks.push(Check::Fail);
+        self
+    }
+
+    fn output(mut self, values: &[Unit]) -> Case {
+        self.checks.push(Check::Output(values.to_vec()));
+        self
+    }
+
+    fn memory(mut self, address: usize, values: &[Unit]) -> Case {
+        self.checks.push(Check::Memory(address, values.to_vec()));
+        self
+    }
+
+    /// Runs the case, returning the first check that failed.
+    fn check<V: Vm>(&self) -> Result<(), String> {
+        let mut vm = V::load(&self.program);
+
+        for check in &self.checks {
+            match check {
+                Check::Input(values) => values.iter().for_each(|value| vm.push_input(*value)),
+                Check::Run(expected) => {
+                    let termination = vm.run();
+
+                    if termination != *expected {
+                        return Err(format!("expected {:?}, got {:?}", expected, termination));
+                    }
+                }
+                Check::Fail => {
+                    let termination = vm.run();
+
+                    if !matches!(termination, Termination::Failed(_)) {
+                        return Err(format!("expected a failure, got {:?}", termination));
+                    }
+                }
+                Check::Output(expected) => {
+                    let output = vm.take_output();
+
+                    if output != *expected {
+                        return Err(format!("expected output {:?}, got {:?}", expected, output));
+                    }
+                }
+                Check::Memory(address, expected) => {
+                    let memory: Vec<Unit> = (*address..address + expected.len()).map(|address| vm.peek(address)).collect();
+
+                    if memory != *expected {
+                        return Err(format!("expected {:?} at {}, got {:?}", expected, address, memory));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The example programs of days 2, 5 and 9, and cases covering every op,
+/// addressing mode and blocking.
+fn cases() -> Vec<Case> {
+    use Feature::*;
+
+    let compare = [
+        3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0, 0, 1002, 21, 125, 20,
+        4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4, 20, 1105, 1, 46, 98, 99,
+    ];
+    let quine = [109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99];
+
+    vec![
+        // Day 2
+        Case::new("day 2, add", &[1, 0, 0, 0, 99]).halts().memory(0, &[2, 0, 0, 0, 99]),
+        Case::new("day 2, multiply", &[2, 3, 0, 3, 99]).halts().memory(0, &[2, 3, 0, 6, 99]),
+        Case::new("day 2, store after halt", &[2, 4, 4, 5, 99, 0]).halts().memory(5, &[9801]),
+        Case::new("day 2, overwrite halt", &[1, 1, 1, 4, 99, 5, 6, 0, 99])
+            .halts()
+            .memory(0, &[30, 1, 1, 4, 2, 5, 6, 0, 99]),
+        Case::new("day 2, example", &[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]).halts().memory(0, &[3500, 9, 10, 70]),
+        // Day 5
+        Case::new("day 5, echo", &[3, 0, 4, 0, 99]).input(&[42]).halts().output(&[42]),
+        Case::new("day 5, immediate mode", &[1002, 4, 3, 4, 33]).halts().memory(4, &[99]),
+        Case::new("day 5, negative values", &[1101, 100, -1, 4, 0]).halts().memory(4, &[99]),
+        Case::new("day 5, equals 8 position", &[3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8]).input(&[8]).halts().output(&[1]),
+        Case::new("day 5, less than 8 position", &[3, 9, 7, 9, 10, 9, 4, 9, 99, -1, 8]).input(&[8]).halts().output(&[0]),
+        Case::new("day 5, equals 8 immediate", &[3, 3, 1108, -1, 8, 3, 4, 3, 99]).input(&[7]).halts().output(&[0]),
+        Case::new("day 5, less than 8 immediate", &[3, 3, 1107, -1, 8, 3, 4, 3, 99]).input(&[7]).halts().output(&[1]),
+        Case::new("day 5, jump position", &[3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9])
+            .input(&[0])
+            .halts()
+            .output(&[0]),
+        Case::new("day 5, jump immediate", &[3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1])
+            .input(&[5])
+            .halts()
+            .output(&[1]),
+        Case::new("day 5, below 8", &compare).input(&[7]).halts().output(&[999]),
+        Case::new("day 5, equal to 8", &compare).input(&[8]).halts().output(&[1000]),
+        Case::new("day 5, above 8", &compare).input(&[9]).halts().output(&[1001]),
+        // Day 9
+        Case::new("day 9, quine", &quine).requires(Relative).requires(ExtendedMemory).halts().output(&quine),
+        Case::new("day 9, 16 digit product", &[1102, 34915192, 34915192, 7, 4, 7, 99, 0])
+            .requires(LargeNumbers)
+            .halts()
+            .output(&[1219070632396864]),
+        Case::new("day 9, large output", &[104, 1125899906842624, 99])
+            .requires(LargeNumbers)
+            .halts()
+            .output(&[1125899906842624]),
+        // Relative base
+        Case::new("relative destination", &[109, 7, 203, 0, 204, 0, 99, 0]).requires(Relative).input(&[7]).halts().output(&[7]),
+        Case::new("negative relative offset", &[109, 5, 21101, 3, 4, -1, 204, -1, 99])
+            .requires(Relative)
+            .halts()
+            .output(&[7])
+            .memory(4, &[7]),
+        Case::new("relative base adds up", &[109, 3, 109, -2, 204, 0, 99]).requires(Relative).halts().output(&[3]),
+        Case::new("relative base from memory", &[9, 7, 204, -1, 99, 0, 42, 7]).requires(Relative).halts().output(&[42]),
+        Case::new("relative base from relative", &[109, 6, 209, 1, 204, -1, 99, 2]).requires(Relative).halts().output(&[2]),
+        Case::new("relative compare", &[109, 15, 21108, 5, 5, 0, 22107, 5, 0, 1, 204, 0, 204, 1, 99, 0, 0])
+            .requires(Relative)
+            .halts()
+            .output(&[1, 0]),
+        Case::new("relative arithmetic", &[109, 11, 22201, -2, -1, 0, 22202, 0, 0, 0, 99, 0, 0])
+            .requires(Relative)
+            .halts()
+            .memory(11, &[9801]),
+        Case::new("relative jumps", &[109, 8, 2106, 0, 0, 104, 1, 99, 7])
+            .requires(Relative)
+            .halts()
+            .output(&[]),
+        Case::new("negative relative base", &[109, -5, 204, 10, 99, 42]).requires(Relative).halts().output(&[42]),
+        Case::new("negative relative address", &[109, -5, 204, 0, 99]).requires(Relative).fails().output(&[]),
+        Case::new("write past the program", &[1101, 1, 2, 1000, 4, 1000, 99]).requires(ExtendedMemory).halts().output(&[3]),
+        Case::new("read past the program", &[4, 1000, 99]).requires(ExtendedMemory).halts().output(&[0]),
+        // Halting and blocking
+        Case::new("halt", &[99]).halts().output(&[]).memory(0, &[99]),
+        Case::new("input in order", &[3, 9, 3, 10, 4, 10, 4, 9, 99, 0, 0]).input(&[1, 2]).halts().output(&[2, 1]),
+        Case::new("blocked and resumed", &[3, 9, 4, 9, 3, 9, 4, 9, 99, 0])
+            .requires(Blocking)
+            .blocks()
+            .output(&[])
+            .memory(0, &[3, 9])
+            .input(&[5])
+            .blocks()
+            .output(&[5])
+            .input(&[6])
+            .halts()
+            .output(&[6]),
+    ]
+}
+
+/// Runs every case the VM supports the features of. Returns the number of
+/// cases run, or a line per failing case. A panic fails the case.
+pub fn conformance<V: Vm>() -> Result<usize, Vec<String>> {
+    let cases: Vec<Case> = cases().into_iter().filter(|case| case.requires.iter().all(|feature| V::supports(*feature))).collect();
+
+    let failures: Vec<String> = cases.iter()
+        .filter_map(|case| {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| case.check::<V>()))
+                .unwrap_or_else(|_| Err(String::from("panicked")));
+
+            result.err().map(|message| format!("{}: {}", case.name, message))
+        })
+        .collect();
+
+    if failures.is_empty() {
+        Ok(cases.len())
+    } else {
+        Err(failures)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_bigint::BigInt;
+
+    #[test]
+    fn test_conformance() {
+        let count = cases().len();
+
+        assert_eq!(conformance::<Computer<i64>>(), Ok(count));
+        assert_eq!(conformance::<Computer<i128>>(), Ok(count));
+        assert_eq!(conformance::<Computer<BigInt>>(), Ok(count));
+    }
+}
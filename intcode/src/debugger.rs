@@ -0,0 +1,559 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::convert::TryFrom;
+use std::io::{self, BufRead, Write};
+use std::ops::Range;
+
+use crate::{Access, Computer, Instruction, IntcodeError, Op, State, Unit, WatchAction, WatchEvent};
+
+const HELP: &str = "\
+s, step [N]              execute N instructions (default 1)
+c, continue              run until a breakpoint, watchpoint, input starvation or halt
+back [N]                 undo N instructions (default 1)
+rc, rcontinue            run backwards until a breakpoint or the start of the history
+b, break ADDR            stop before executing the instruction at ADDR
+b, break MNEMONIC        stop before any instruction with that opcode, e.g. OUT
+d, delete ADDR|MNEMONIC  remove a breakpoint
+w, watch ADDR [LEN]      stop after an instruction writes to LEN words at ADDR
+unwatch ID               remove a watchpoint
+i, info                  show pc, relative base, state, breakpoints and watchpoints
+writer ADDR              show the last instruction that wrote to ADDR
+x ADDR [LEN]             show LEN memory words starting at ADDR
+l, list [ADDR] [LEN]     disassemble LEN instructions starting at ADDR (default pc)
+poke ADDR VALUE          write VALUE to memory at ADDR
+in [VALUE...]            show the input queue, or push values onto it
+line TEXT                push TEXT as ASCII followed by a newline
+out [text|clear]         show the output queue, as text, or drop it
+h, help                  show this help
+q, quit                  leave the debugger
+An empty line repeats the last step, continue or their backward versions.";
+
+/// The number of instructions the debugger can step back over.
+const HISTORY_LIMIT: usize = 1 << 20;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Stop {
+    Stepped,
+    Breakpoint(usize),
+    OpBreakpoint(Op),
+    Watchpoint(WatchEvent),
+    Blocked,
+    Halted,
+    /// Stepping back reached the oldest instruction in the history.
+    HistoryStart,
+}
+
+/// `address..address + length`, or `None` if it does not fit in a `usize`.
+fn address_range(address: Unit, length: Unit) -> Option<Range<usize>> {
+    let address = usize::try_from(address).ok()?;
+    Some(address..address.checked_add(usize::try_from(length).ok()?)?)
+}
+
+/// Wraps a `Computer` with breakpoints and single stepping, forwards and
+/// backwards, driven either programmatically or from the line oriented
+/// `repl`.
+pub struct Debugger {
+    computer: Computer,
+    breakpoints: BTreeSet<usize>,
+    op_breakpoints: HashSet<Op>,
+    watchpoints: BTreeMap<usize, Range<usize>>,
+    last_command: Option<String>,
+}
+
+impl Debugger {
+    /// Starts a bounded history on `computer` unless it already keeps one.
+    pub fn new(mut computer: Computer) -> Debugger {
+        if computer.get_history().is_none() {
+            computer.start_history(Some(HISTORY_LIMIT));
+        }
+
+        Debugger {
+            computer,
+            breakpoints: BTreeSet::new(),
+            op_breakpoints: HashSet::new(),
+            watchpoints: BTreeMap::new(),
+            last_command: None,
+        }
+    }
+
+    pub fn computer(&self) -> &Computer {
+        &self.computer
+    }
+
+    pub fn computer_mut(&mut self) -> &mut Computer {
+        &mut self.computer
+    }
+
+    pub fn into_computer(self) -> Computer {
+        self.computer
+    }
+
+    pub fn add_breakpoint(&mut self, pc: usize) {
+        self.breakpoints.insert(pc);
+    }
+
+    pub fn remove_breakpoint(&mut self, pc: usize) -> bool {
+        self.breakpoints.remove(&pc)
+    }
+
+    pub fn add_op_breakpoint(&mut self, op: Op) {
+        self.op_breakpoints.insert(op);
+    }
+
+    pub fn remove_op_breakpoint(&mut self, op: Op) -> bool {
+        self.op_breakpoints.remove(&op)
+    }
+
+    /// Stops after any instruction that writes to `addresses`. Returns an id
+    /// for `remove_watchpoint`.
+    pub fn add_watchpoint(&mut self, addresses: Range<usize>) -> usize {
+        let id = self.computer.add_watchpoint(addresses.clone(), Access::Write, |_| WatchAction::Pause);
+        self.watchpoints.insert(id, addresses);
+        id
+    }
+
+    pub fn remove_watchpoint(&mut self, id: usize) -> bool {
+        self.watchpoints.remove(&id).is_some() && self.computer.remove_watchpoint(id)
+    }
+
+    fn breakpoint_at_pc(&self) -> Option<Stop> {
+        let pc = self.computer.get_pc();
+
+        if self.breakpoints.contains(&pc) {
+            return Some(Stop::Breakpoint(pc));
+        }
+
+        match Op::decode(self.computer.peek(pc)) {
+            Ok(op) if self.op_breakpoints.contains(&op) => Some(Stop::OpBreakpoint(op)),
+            _ => None,
+        }
+    }
+
+    /// Executes one instruction, and returns why execution should stop
+    /// there, if it should.
+    fn step_once(&mut self) -> Result<Option<Stop>, IntcodeError> {
+        Ok(match self.computer.step()? {
+            State::Paused => self.computer.get_watch_event().cloned().map(Stop::Watchpoint),
+            State::Blocked => Some(Stop::Blocked),
+            State::Halted => Some(Stop::Halted),
+            _ => None,
+        })
+    }
+
+    /// Executes up to `count` instructions, ignoring breakpoints but not
+    /// watchpoints.
+    pub fn step(&mut self, count: usize) -> Result<Stop, IntcodeError> {
+        for _ in 0..count {
+            if let Some(stop) = self.step_once()? {
+                return Ok(stop);
+            }
+        }
+
+        Ok(Stop::Stepped)
+    }
+
+    /// Runs until a breakpoint or watchpoint is hit, or the computer waits
+    /// for input or halts. A breakpoint at the current pc does not stop
+    /// execution again.
+    pub fn resume(&mut self) -> Result<Stop, IntcodeError> {
+        if let Some(stop) = self.step_once()? {
+            return Ok(stop);
+        }
+
+        loop {
+            if let Some(stop) = self.breakpoint_at_pc() {
+                return Ok(stop);
+            }
+
+            if let Some(stop) = self.step_once()? {
+                return Ok(stop);
+            }
+        }
+    }
+
+    /// Undoes up to `count` instructions.
+    pub fn step_back(&mut self, count: usize) -> Stop {
+        for _ in 0..count {
+            if !self.computer.step_back() {
+                return Stop::HistoryStart;
+            }
+        }
+
+        Stop::Stepped
+    }
+
+    /// Steps back until the next instruction is at a breakpoint, ignoring
+    /// one at the current pc like `resume`.
+    pub fn reverse(&mut self) -> Stop {
+        loop {
+            if !self.computer.step_back() {
+                return Stop::HistoryStart;
+            }
+
+            if let Some(stop) = self.breakpoint_at_pc() {
+                return stop;
+            }
+        }
+    }
+
+    fn format_at(&self, address: usize) -> (String, usize) {
+        let window: Vec<Unit> = (address..address.saturating_add(4)).map(|address| self.computer.peek(address)).collect();
+
+        match Instruction::decode(&window, 0) {
+            Some(mut instruction) => {
+                instruction.address = address;
+                (instruction.to_string(), instruction.size())
+            }
+            None => (format!(".data {}", self.computer.peek(address)), 1),
+        }
+    }
+
+    fn print_location(&self, out: &mut impl Write) -> io::Result<()> {
+        let pc = self.computer.get_pc();
+        writeln!(out, "=> {:>6}  {}", pc, self.format_at(pc).0)
+    }
+
+    fn print_stop(&self, result: Result<Stop, IntcodeError>, out: &mut impl Write) -> io::Result<()> {
+        match result {
+            Ok(Stop::Stepped) => (),
+            Ok(Stop::Breakpoint(pc)) => writeln!(out, "Breakpoint at {}", pc)?,
+            Ok(Stop::OpBreakpoint(op)) => writeln!(out, "Breakpoint on {}", op.mnemonic())?,
+            Ok(Stop::Watchpoint(event)) => writeln!(
+                out,
+                "Watchpoint {}: [{}] {} -> {} at {}",
+                event.watchpoint, event.address, event.old, event.new, event.pc
+            )?,
+            Ok(Stop::Blocked) => writeln!(out, "Blocked on input")?,
+            Ok(Stop::Halted) => writeln!(out, "Halted")?,
+            Ok(Stop::HistoryStart) => writeln!(out, "At the start of the history")?,
+            Err(e) => writeln!(out, "Error: {}", e)?,
+        }
+
+        if !self.computer.get_output().is_empty() {
+            writeln!(out, "{} value(s) in output queue", self.computer.get_output().len())?;
+        }
+
+        self.print_location(out)
+    }
+
+    fn print_info(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "pc: {}", self.computer.get_pc())?;
+        writeln!(out, "rb: {}", self.computer.get_relative_base())?;
+        writeln!(out, "state: {:?}", self.computer.get_state())?;
+        writeln!(out, "input: {} value(s), output: {} value(s)", self.computer.get_input().len(), self.computer.get_output().len())?;
+
+        let mut ops: Vec<&Op> = self.op_breakpoints.iter().collect();
+        ops.sort_by_key(|op| op.code());
+
+        let breakpoints = self.breakpoints.iter()
+            .map(|pc| pc.to_string())
+            .chain(ops.iter().map(|op| op.mnemonic().to_string()))
+            .collect::<Vec<String>>();
+
+        writeln!(out, "breakpoints: {}", breakpoints.join(" "))?;
+
+        let watchpoints = self.watchpoints.iter()
+            .map(|(id, addresses)| format!("{}:{}..{}", id, addresses.start, addresses.end))
+            .collect::<Vec<String>>();
+
+        writeln!(out, "watchpoints: {}", watchpoints.join(" "))
+    }
+
+    fn print_memory(&self, addresses: Range<usize>, out: &mut impl Write) -> io::Result<()> {
+        for row in addresses.clone().step_by(8) {
+            let values = (row..row.saturating_add(8).min(addresses.end))
+                .map(|a| format!("{:>8}", self.computer.peek(a)))
+                .collect::<String>();
+
+            writeln!(out, "{:>6}: {}", row, values)?;
+        }
+
+        Ok(())
+    }
+
+    fn print_listing(&self, mut address: usize, length: usize, out: &mut impl Write) -> io::Result<()> {
+        for _ in 0..length {
+            let (text, size) = self.format_at(address);
+            let marker = if address == self.computer.get_pc() { "=>" } else { "  " };
+            let breakpoint = if self.breakpoints.contains(&address) { "*" } else { " " };
+
+            writeln!(out, "{}{}{:>6}  {}", marker, breakpoint, address, text)?;
+
+            match address.checked_add(size) {
+                Some(next) => address = next,
+                None => break,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn print_values(values: impl Iterator<Item = Unit>, out: &mut impl Write) -> io::Result<()> {
+        let values = values.map(|value| value.to_string()).collect::<Vec<String>>();
+        writeln!(out, "[{}]", values.join(", "))
+    }
+
+    /// Executes one REPL command. Returns `false` when the user asks to quit.
+    pub fn execute(&mut self, line: &str, out: &mut impl Write) -> io::Result<bool> {
+        let line = match (line.trim(), &self.last_command) {
+            ("", Some(last)) => last.clone(),
+            (line, _) => line.to_string(),
+        };
+
+        let (command, rest) = match line.find(char::is_whitespace) {
+            Some(index) => (&line[..index], line[index..].trim()),
+            None => (line.as_str(), ""),
+        };
+        let args: Vec<&str> = rest.split_whitespace().collect();
+        let numbers: Result<Vec<Unit>, _> = args.iter().map(|arg| arg.parse::<Unit>()).collect();
+
+        match (command, numbers.as_deref(), args.as_slice()) {
+            ("", _, _) => (),
+            ("s", Ok(numbers), _) | ("step", Ok(numbers), _) if numbers.len() <= 1 && numbers.iter().all(|n| *n >= 0) => {
+                let count = numbers.first().map_or(1, |n| *n as usize);
+                let result = self.step(count);
+                self.print_stop(result, out)?;
+                self.last_command = Some(line.clone());
+            }
+            ("c", _, []) | ("continue", _, []) => {
+                let result = self.resume();
+                self.print_stop(result, out)?;
+                self.last_command = Some(line.clone());
+            }
+            ("back", Ok(numbers), _) if numbers.len() <= 1 && numbers.iter().all(|n| *n >= 0) => {
+                let count = numbers.first().map_or(1, |n| *n as usize);
+                let stop = self.step_back(count);
+                self.print_stop(Ok(stop), out)?;
+                self.last_command = Some(line.clone());
+            }
+            ("rc", _, []) | ("rcontinue", _, []) => {
+                let stop = self.reverse();
+                self.print_stop(Ok(stop), out)?;
+                self.last_command = Some(line.clone());
+            }
+            ("b", Ok([pc]), _) | ("break", Ok([pc]), _) => match usize::try_from(*pc) {
+                Ok(pc) => self.add_breakpoint(pc),
+                Err(_) => writeln!(out, "Invalid address: {}", pc)?,
+            },
+            ("b", _, [mnemonic]) | ("break", _, [mnemonic]) if Op::from_mnemonic(mnemonic).is_some() => {
+                self.add_op_breakpoint(Op::from_mnemonic(mnemonic).unwrap());
+            }
+            ("d", Ok([pc]), _) | ("delete", Ok([pc]), _) => {
+                if !usize::try_from(*pc).is_ok_and(|pc| self.remove_breakpoint(pc)) {
+                    writeln!(out, "No breakpoint at {}", pc)?;
+                }
+            }
+            ("d", _, [mnemonic]) | ("delete", _, [mnemonic]) if Op::from_mnemonic(mnemonic).is_some() => {
+                if !self.remove_op_breakpoint(Op::from_mnemonic(mnemonic).unwrap()) {
+                    writeln!(out, "No breakpoint on {}", mnemonic)?;
+                }
+            }
+            ("w", Ok(numbers @ [_]), _) | ("watch", Ok(numbers @ [_]), _)
+            | ("w", Ok(numbers @ [_, _]), _) | ("watch", Ok(numbers @ [_, _]), _) => {
+                match address_range(numbers[0], numbers.get(1).copied().unwrap_or(1)).filter(|range| !range.is_empty()) {
+                    Some(addresses) => writeln!(out, "Watchpoint {}", self.add_watchpoint(addresses))?,
+                    None => writeln!(out, "Invalid range: {}", rest)?,
+                }
+            }
+            ("unwatch", Ok([id]), _) => {
+                if !usize::try_from(*id).is_ok_and(|id| self.remove_watchpoint(id)) {
+                    writeln!(out, "No watchpoint {}", id)?;
+                }
+            }
+            ("writer", Ok([address]), _) => {
+                let history = self.computer.get_history();
+
+                match usize::try_from(*address).ok().and_then(|address| history.and_then(|history| history.last_write(address))) {
+                    Some(record) => writeln!(out, "Step {} at {}: {}", record.step, record.pc, self.format_at(record.pc).0)?,
+                    None => writeln!(out, "No write to {} in the history", address)?,
+                }
+            }
+            ("i", _, []) | ("info", _, []) => self.print_info(out)?,
+            ("x", Ok(numbers @ [_]), _) | ("x", Ok(numbers @ [_, _]), _) => {
+                match address_range(numbers[0], numbers.get(1).copied().unwrap_or(1)) {
+                    Some(addresses) => self.print_memory(addresses, out)?,
+                    None => writeln!(out, "Invalid range: {}", rest)?,
+                }
+            }
+            ("l", Ok(numbers), _) | ("list", Ok(numbers), _) if numbers.len() <= 2 => {
+                let address = numbers.first().map_or(Ok(self.computer.get_pc()), |n| usize::try_from(*n));
+                let length = numbers.get(1).map_or(Ok(10), |n| usize::try_from(*n));
+
+                match (address, length) {
+                    (Ok(address), Ok(length)) => self.print_listing(address, length, out)?,
+                    _ => writeln!(out, "Invalid range: {}", rest)?,
+                }
+            }
+            ("poke", Ok([address, value]), _) => match usize::try_from(*address) {
+                Ok(address) => {
+                    if let Err(e) = self.computer.poke(address, *value) {
+                        writeln!(out, "{}", e)?;
+                    }
+                }
+                Err(_) => writeln!(out, "Invalid address: {}", address)?,
+            },
+            ("in", Ok([]), _) => Debugger::print_values(self.computer.get_input().iter().copied(), out)?,
+            ("in", Ok(values), _) => values.iter().for_each(|value| self.computer.push_input(*value)),
+            ("line", _, _) => self.computer.println(rest.to_string()),
+            ("out", _, []) => Debugger::print_values(self.computer.get_output().iter().copied(), out)?,
+            ("out", _, ["text"]) => {
+                let text: String = self.computer.get_output().iter()
+                    .map(|value| match u8::try_from(*value) {
+                        Ok(c) => (c as char).to_string(),
+                        Err(_) => value.to_string(),
+                    })
+                    .collect();
+                write!(out, "{}", text)?;
+            }
+            ("out", _, ["clear"]) => while self.computer.pop_output().is_some() {},
+            ("h", _, _) | ("help", _, _) => writeln!(out, "{}", HELP)?,
+            ("q", _, _) | ("quit", _, _) => return Ok(false),
+            _ => writeln!(out, "Unknown command: {} (try help)", line)?,
+        }
+
+        Ok(true)
+    }
+
+    /// Reads commands from `input` until it is exhausted or the user quits.
+    pub fn repl(&mut self, mut input: impl BufRead, mut out: impl Write) -> io::Result<()> {
+        self.print_location(&mut out)?;
+
+        let mut line = String::new();
+
+        loop {
+            write!(out, "(intcode) ")?;
+            out.flush()?;
+
+            line.clear();
+            if input.read_line(&mut line)? == 0 {
+                break;
+            }
+
+            if !self.execute(line.trim_end_matches(['\n', '\r']), &mut out)? {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assemble;
+
+    fn debugger() -> Debugger {
+        let memory = assemble("
+                    IN -> [counter]
+            loop:   OUT [counter]
+                    ADD [counter] #-1 -> [counter]
+                    JNZ [counter] #loop
+                    HALT
+            counter: .data 0
+        ").unwrap();
+
+        Debugger::new(Computer::new(&memory, None))
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut debugger = debugger();
+        debugger.add_breakpoint(2);
+
+        assert_eq!(debugger.resume(), Ok(Stop::Blocked));
+        debugger.computer_mut().push_input(2);
+        assert_eq!(debugger.resume(), Ok(Stop::Breakpoint(2)));
+        assert_eq!(debugger.resume(), Ok(Stop::Breakpoint(2)));
+
+        debugger.remove_breakpoint(2);
+        debugger.add_op_breakpoint(Op::Halt);
+        assert_eq!(debugger.resume(), Ok(Stop::OpBreakpoint(Op::Halt)));
+        assert_eq!(debugger.computer().get_output().iter().copied().collect::<Vec<Unit>>(), vec![2, 1]);
+        assert_eq!(debugger.resume(), Ok(Stop::Halted));
+    }
+
+    #[test]
+    fn test_repl() {
+        let mut debugger = debugger();
+        let mut out = Vec::new();
+
+        debugger.repl("in 5\nb 8\nc\n\npoke 12 1\nx 12\nc\nout\nq\n".as_bytes(), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.contains("Breakpoint at 8\n1 value(s) in output queue\n=>      8  JNZ [12] #2"));
+        assert!(out.contains("    12:        1\n"));
+        assert!(out.contains("[5, 4, 1]"));
+    }
+
+    #[test]
+    fn test_repl_prompts_before_reading() {
+        let mut debugger = debugger();
+        let mut out = Vec::new();
+
+        debugger.repl("in 257\nc\nout text\n".as_bytes(), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.starts_with("=>      0  IN -> [12]\n(intcode) (intcode) "));
+        assert!(out.contains("(intcode) 257256\u{ff}\u{fe}"));
+        assert!(out.ends_with("(intcode) "));
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        let mut debugger = debugger();
+        let mut out = Vec::new();
+
+        debugger.repl("in 3\nw 12\nc\nc\nunwatch 0\nc\nq\n".as_bytes(), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.contains("Watchpoint 0: [12] 0 -> 3 at 0\n=>      2  OUT [12]"));
+        assert!(out.contains("Watchpoint 0: [12] 3 -> 2 at 4\n1 value(s) in output queue"));
+        assert!(out.contains("Halted"));
+    }
+
+    #[test]
+    fn test_reverse() {
+        let mut debugger = debugger();
+        let mut out = Vec::new();
+
+        debugger.repl("in 2\nc\nwriter 12\nb 2\nrc\nback 9\nq\n".as_bytes(), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.contains("Halted\n2 value(s) in output queue\n"));
+        assert!(out.contains("Step 6 at 4: ADD [12] #-1 -> [12]\n"));
+        assert!(out.contains("Breakpoint at 2\n1 value(s) in output queue\n=>      2  OUT [12]\n"));
+        assert!(out.contains("At the start of the history\n=>      0  IN -> [12]\n"));
+        assert_eq!(debugger.computer().get_input().iter().copied().collect::<Vec<Unit>>(), vec![2]);
+    }
+
+    #[test]
+    fn test_negative_counts() {
+        let mut debugger = debugger();
+        let mut out = Vec::new();
+
+        debugger.repl("s -1\nback -2\nq\n".as_bytes(), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.contains("Unknown command: s -1 (try help)\n"));
+        assert!(out.contains("Unknown command: back -2 (try help)\n"));
+        assert_eq!(debugger.computer().get_steps(), 0);
+    }
+
+    #[test]
+    fn test_invalid_addresses() {
+        let mut debugger = debugger();
+        let mut out = Vec::new();
+
+        let commands = "x -1\nx 0 1000000000000000000000000000000\nw 18446744073709551615 2\nw 3 0\nl -3\npoke -1 5\nb -1\nq\n";
+        debugger.repl(commands.as_bytes(), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.contains("Invalid range: -1\n"));
+        assert!(out.contains("Invalid range: 0 1000000000000000000000000000000\n"));
+        assert!(out.contains("Invalid range: 18446744073709551615 2\n"));
+        assert!(out.contains("Invalid range: 3 0\n"));
+        assert!(out.contains("Invalid range: -3\n"));
+        assert!(out.contains("Invalid address: -1\n(intcode) Invalid address: -1\n"));
+        assert_eq!(debugger.computer().peek(0), 3);
+    }
+}
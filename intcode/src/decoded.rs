@@ -0,0 +1,109 @@
+use std::sync::Arc;
+
+use crate::{get_addressing_mode, AddressingMode, Op, Unit};
+
+/// Programs rarely execute code this far out, so higher addresses are
+/// decoded on every visit rather than growing the cache.
+const MAX_CACHED_ADDRESS: usize = 1 << 20;
+
+/// The opcode word at some address, split into its operation and the
+/// addressing modes of its parameters. A mode is `None` if its digit is
+/// invalid, which is only an error once the parameter is used.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) struct Decoded {
+    pub opcode: Unit,
+    pub op: Op,
+    pub modes: [Option<AddressingMode>; 3],
+}
+
+impl Decoded {
+    pub fn new(opcode: Unit) -> Option<Decoded> {
+        let op = Op::decode(opcode).ok()?;
+        let mut modes = [None; 3];
+
+        for (position, mode) in modes.iter_mut().enumerate().take(op.num_params().0) {
+            *mode = get_addressing_mode(opcode, position);
+        }
+
+        Some(Decoded { opcode, op, modes })
+    }
+}
+
+/// The number of entries in a page of the cache, the unit copied when a
+/// clone changes a page it shares.
+const PAGE_SIZE: usize = 256;
+
+type Page = Arc<Vec<Option<Decoded>>>;
+
+/// Decoded opcodes keyed by pc. Only the opcode word is cached, operands are
+/// always read from memory, so a write only has to drop the entry at the
+/// written address. Clones share pages until one of them changes a page.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DecodeCache {
+    pages: Vec<Option<Page>>,
+}
+
+impl DecodeCache {
+    pub fn get(&self, pc: usize) -> Option<Decoded> {
+        self.pages.get(pc / PAGE_SIZE)?.as_ref()?[pc % PAGE_SIZE]
+    }
+
+    pub fn insert(&mut self, pc: usize, decoded: Decoded) {
+        if pc >= MAX_CACHED_ADDRESS {
+            return;
+        }
+
+        let index = pc / PAGE_SIZE;
+
+        if index >= self.pages.len() {
+            self.pages.resize(index + 1, None);
+        }
+
+        let page = self.pages[index].get_or_insert_with(|| Arc::new(vec![None; PAGE_SIZE]));
+        Arc::make_mut(page)[pc % PAGE_SIZE] = Some(decoded);
+    }
+
+    pub fn invalidate(&mut self, address: usize) {
+        if self.get(address).is_some() {
+            if let Some(Some(page)) = self.pages.get_mut(address / PAGE_SIZE) {
+                Arc::make_mut(page)[address % PAGE_SIZE] = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shared(a: &DecodeCache, b: &DecodeCache, index: usize) -> bool {
+        match (&a.pages[index], &b.pages[index]) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn test_clone_shares_pages() {
+        let mut cache = DecodeCache::default();
+        cache.insert(0, Decoded::new(1101).unwrap());
+        cache.insert(4, Decoded::new(99).unwrap());
+        cache.insert(PAGE_SIZE, Decoded::new(99).unwrap());
+
+        let mut copy = cache.clone();
+        assert!(shared(&cache, &copy, 0) && shared(&cache, &copy, 1));
+
+        // Dropping an entry that is not there leaves the page shared
+        copy.invalidate(2);
+        assert!(shared(&cache, &copy, 0));
+
+        // Changing a page copies that page only
+        copy.invalidate(0);
+        copy.insert(PAGE_SIZE * 2, Decoded::new(1101).unwrap());
+        assert!(!shared(&cache, &copy, 0) && shared(&cache, &copy, 1));
+        assert_eq!(copy.get(0), None);
+        assert_eq!(cache.get(0), Decoded::new(1101));
+        assert_eq!(copy.get(4), Decoded::new(99));
+        assert_eq!(cache.get(PAGE_SIZE * 2), None);
+    }
+}
@@ -0,0 +1,709 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::fmt::{self, Write};
+
+use crate::{label_name, AddressingMode, Block, ControlFlowGraph, EdgeKind, Exit, Instruction, Op, Operand, Unit};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Expr {
+    Const(Unit),
+    Var(String),
+    Mem(Box<Expr>),
+    Binary(Box<Expr>, &'static str, Box<Expr>),
+    Input,
+}
+
+impl Expr {
+    /// Builds `left op right`, folding constants and identities.
+    fn binary(left: Expr, op: &'static str, right: Expr) -> Expr {
+        use Expr::Const;
+
+        let folded = match (op, &left, &right) {
+            ("+", Const(a), Const(b)) => a.checked_add(*b).map(Const),
+            ("*", Const(a), Const(b)) => a.checked_mul(*b).map(Const),
+            ("==", Const(a), Const(b)) => Some(Const((a == b) as Unit)),
+            ("<", Const(a), Const(b)) => Some(Const((a < b) as Unit)),
+            ("+", Const(0), _) | ("*", Const(1), _) => Some(right.clone()),
+            ("+", _, Const(0)) | ("*", _, Const(1)) => Some(left.clone()),
+            ("*", Const(0), _) | ("*", _, Const(0)) => Some(Const(0)),
+            ("+", _, Const(b)) if *b < 0 => b.checked_neg().map(|b| Expr::Binary(Box::new(left.clone()), "-", Box::new(Const(b)))),
+            _ => None,
+        };
+
+        folded.unwrap_or_else(|| Expr::Binary(Box::new(left), op, Box::new(right)))
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let operand = |f: &mut fmt::Formatter, expr: &Expr| match expr {
+            Expr::Binary(..) => write!(f, "({})", expr),
+            _ => write!(f, "{}", expr),
+        };
+
+        match self {
+            Expr::Const(value) => write!(f, "{}", value),
+            Expr::Var(name) => write!(f, "{}", name),
+            Expr::Mem(address) => write!(f, "mem[{}]", address),
+            Expr::Binary(left, op, right) => {
+                operand(f, left)?;
+                write!(f, " {} ", op)?;
+                operand(f, right)
+            }
+            Expr::Input => write!(f, "input()"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Cond {
+    left: Expr,
+    op: &'static str,
+    right: Expr,
+}
+
+impl Cond {
+    fn negate(self) -> Cond {
+        let op = match self.op {
+            "==" => "!=",
+            "!=" => "==",
+            "<" => ">=",
+            _ => "<",
+        };
+
+        Cond { op, ..self }
+    }
+}
+
+impl fmt::Display for Cond {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {}", self.left, self.op, self.right)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Place {
+    /// `[rb+k]` for `k >= 0` in a function, where it passes arguments to
+    /// the next call.
+    Out(Unit),
+    Other(Expr),
+}
+
+impl fmt::Display for Place {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Place::Out(slot) => write!(f, "out{}", slot),
+            Place::Other(expr) => write!(f, "{}", expr),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Stmt {
+    Assign(Place, Expr),
+    Output(Expr),
+    Print(String),
+    MoveBase(Expr),
+}
+
+#[derive(Debug, Clone)]
+enum Term {
+    Fall(usize),
+    Goto(usize),
+    Branch(Cond, usize),
+    Call(usize, Vec<Expr>),
+    Return(Option<Cond>),
+    IndirectGoto(Option<Cond>, Expr),
+    Halt,
+    Invalid(usize),
+}
+
+#[derive(Debug, Clone)]
+struct BlockIr {
+    start: usize,
+    stmts: Vec<Stmt>,
+    term: Term,
+}
+
+impl BlockIr {
+    fn jumps_back_to(&self, header: usize) -> bool {
+        self.start >= header && match self.term {
+            Term::Goto(target) | Term::Branch(_, target) => target == header,
+            _ => false,
+        }
+    }
+}
+
+/// A function found in the control-flow graph: the entry point and every
+/// block reached from it without following calls. `frame` is the amount
+/// its prologue moves the relative base by.
+struct Function {
+    entry: usize,
+    frame: Unit,
+    params: usize,
+    blocks: Vec<usize>,
+}
+
+impl Function {
+    fn name(&self) -> String {
+        function_name(self.entry)
+    }
+
+    /// Names a relative address as seen from inside the function.
+    fn relative(&self, offset: Unit) -> Place {
+        let slot = offset.checked_add(self.frame).filter(|slot| self.frame > 0 && *slot > 0);
+
+        match slot {
+            _ if offset >= 0 => Place::Out(offset),
+            Some(slot) if slot as usize <= self.params => Place::Other(Expr::Var(format!("arg{}", slot))),
+            Some(slot) => Place::Other(Expr::Var(format!("local{}", slot))),
+            None => Place::Other(Expr::Var(format!("rb[{}]", offset))),
+        }
+    }
+}
+
+fn function_name(entry: usize) -> String {
+    match entry {
+        0 => String::from("main"),
+        entry => format!("f{}", entry),
+    }
+}
+
+fn print_string(values: &[Unit]) -> Option<String> {
+    values.iter()
+        .map(|value| match value {
+            10 => Some(String::from("\\n")),
+            34 => Some(String::from("\\\"")),
+            92 => Some(String::from("\\\\")),
+            32..=126 => Some((*value as u8 as char).to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Converts the instructions of a block into statements. A write to an
+/// operand of a later instruction in the same block, which is how Intcode
+/// dereferences pointers, is folded into that operand instead. Operands
+/// that are in `written`, the addresses the program writes to, are
+/// variables of their own and are read from memory.
+fn convert(function: &Function, block: &Block, written: &BTreeSet<usize>) -> BlockIr {
+    let operand_words: BTreeSet<usize> = block.instructions.iter()
+        .flat_map(|instruction| instruction.address + 1..instruction.address + instruction.size())
+        .collect();
+
+    let mut patches: HashMap<usize, Expr> = HashMap::new();
+    let mut stmts: Vec<Stmt> = Vec::new();
+
+    let patched = |patches: &mut HashMap<usize, Expr>, word: usize| {
+        patches.remove(&word).or_else(|| if written.contains(&word) { Some(Expr::Mem(Box::new(Expr::Const(word as Unit)))) } else { None })
+    };
+
+    let operand_expr = |patches: &mut HashMap<usize, Expr>, instruction: &Instruction, position: usize| {
+        let Operand { mode, value } = instruction.operands[position];
+        let patch = patched(patches, instruction.address + 1 + position);
+
+        match (mode, patch) {
+            (AddressingMode::Immediate, Some(patch)) => patch,
+            (AddressingMode::Immediate, None) => Expr::Const(value),
+            (AddressingMode::Absolute, Some(patch)) => Expr::Mem(Box::new(patch)),
+            (AddressingMode::Absolute, None) => Expr::Mem(Box::new(Expr::Const(value))),
+            (AddressingMode::Relative, Some(patch)) => {
+                Expr::Mem(Box::new(Expr::binary(Expr::Var(String::from("rb")), "+", patch)))
+            }
+            (AddressingMode::Relative, None) => match function.relative(value) {
+                Place::Other(expr) => expr,
+                out => Expr::Var(out.to_string()),
+            },
+        }
+    };
+
+    let last = block.instructions.last().unwrap();
+
+    for (index, instruction) in block.instructions.iter().enumerate() {
+        let is_last = index == block.instructions.len() - 1;
+
+        let value = match instruction.op {
+            Op::Add => Some(Expr::binary(operand_expr(&mut patches, instruction, 0), "+", operand_expr(&mut patches, instruction, 1))),
+            Op::Mul => Some(Expr::binary(operand_expr(&mut patches, instruction, 0), "*", operand_expr(&mut patches, instruction, 1))),
+            Op::LessThan => Some(Expr::binary(operand_expr(&mut patches, instruction, 0), "<", operand_expr(&mut patches, instruction, 1))),
+            Op::Equals => Some(Expr::binary(operand_expr(&mut patches, instruction, 0), "==", operand_expr(&mut patches, instruction, 1))),
+            Op::Input => Some(Expr::Input),
+            Op::Output => {
+                stmts.push(Stmt::Output(operand_expr(&mut patches, instruction, 0)));
+                None
+            }
+            Op::ModRel => {
+                match operand_expr(&mut patches, instruction, 0) {
+                    Expr::Const(offset) if index == 0 && offset == function.frame && block.start == function.entry => (),
+                    offset => stmts.push(Stmt::MoveBase(offset)),
+                }
+                None
+            }
+            Op::JumpNZ | Op::JumpZ | Op::Halt if is_last => None,
+            Op::JumpNZ | Op::JumpZ | Op::Halt => unreachable!("Blocks end at jumps and halts"),
+        };
+
+        if let Some(value) = value {
+            let position = instruction.operands.len() - 1;
+            let dest = instruction.operands[position];
+            let dest_address = instruction.address + 1 + position;
+
+            let place = match (dest.mode, patched(&mut patches, dest_address)) {
+                (AddressingMode::Absolute, None) if dest.value >= 0 && operand_words.contains(&(dest.value as usize))
+                    && dest.value as usize > instruction.address + position + 1 => {
+                    patches.insert(dest.value as usize, value);
+                    continue;
+                }
+                (AddressingMode::Absolute, None) => Place::Other(Expr::Mem(Box::new(Expr::Const(dest.value)))),
+                (AddressingMode::Absolute, Some(patch)) => Place::Other(Expr::Mem(Box::new(patch))),
+                (AddressingMode::Relative, None) => function.relative(dest.value),
+                (_, patch) => Place::Other(Expr::Mem(Box::new(Expr::binary(
+                    Expr::Var(String::from("rb")),
+                    "+",
+                    patch.unwrap_or(Expr::Const(dest.value)),
+                )))),
+            };
+
+            stmts.push(Stmt::Assign(place, value));
+        }
+    }
+
+    let cond = |stmts: &mut Vec<Stmt>, patches: &mut HashMap<usize, Expr>| {
+        let value = operand_expr(patches, last, 0);
+        let folded = match stmts.last() {
+            Some(Stmt::Assign(place, Expr::Binary(left, op, right))) if place.to_string() == value.to_string() && (*op == "==" || *op == "<") => {
+                Some(Cond { left: (**left).clone(), op, right: (**right).clone() })
+            }
+            _ => None,
+        };
+
+        let cond = match folded {
+            Some(cond) => {
+                stmts.pop();
+                cond
+            }
+            None => Cond { left: value, op: "!=", right: Expr::Const(0) },
+        };
+
+        match last.op {
+            Op::JumpZ => cond.negate(),
+            _ => cond,
+        }
+    };
+
+    let unconditional = !last.falls_through();
+    let end = block.end();
+
+    let term = match block.exit {
+        Exit::Halt => Term::Halt,
+        Exit::Fallthrough => Term::Fall(end),
+        Exit::Invalid => Term::Invalid(end),
+        Exit::Jump if !last.may_jump() => Term::Fall(end),
+        Exit::Jump if unconditional => Term::Goto(last.jump_target().unwrap()),
+        Exit::Jump => Term::Branch(cond(&mut stmts, &mut patches), last.jump_target().unwrap()),
+        Exit::Call => {
+            let mut args: BTreeMap<Unit, Expr> = BTreeMap::new();
+
+            stmts.retain(|stmt| match stmt {
+                Stmt::Assign(Place::Out(slot), value) => {
+                    if *slot > 0 {
+                        args.insert(*slot, value.clone());
+                    }
+                    false
+                }
+                _ => true,
+            });
+
+            Term::Call(last.jump_target().unwrap(), args.into_values().collect())
+        }
+        Exit::Return => {
+            if let Some(Stmt::MoveBase(Expr::Const(offset))) = stmts.last() {
+                if *offset == -function.frame {
+                    stmts.pop();
+                }
+            }
+
+            Term::Return(if unconditional { None } else { Some(cond(&mut stmts, &mut patches)) })
+        }
+        Exit::Indirect => {
+            let target = operand_expr(&mut patches, last, 1);
+            let cond = if unconditional { None } else { Some(cond(&mut stmts, &mut patches)) };
+            Term::IndirectGoto(cond, target)
+        }
+    };
+
+    let mut folded: Vec<Stmt> = Vec::new();
+
+    for stmt in stmts {
+        let text = match &stmt {
+            Stmt::Output(Expr::Const(value)) => print_string(&[*value]),
+            _ => None,
+        };
+
+        match (folded.last_mut(), text) {
+            (Some(Stmt::Print(string)), Some(text)) => string.push_str(&text),
+            (_, Some(text)) => folded.push(Stmt::Print(text)),
+            (_, None) => folded.push(stmt),
+        }
+    }
+
+    BlockIr { start: block.start, stmts: folded, term }
+}
+
+enum Line {
+    Label(usize, usize),
+    Text(usize, String),
+}
+
+/// Lays out the blocks of one function as nested `if`, `while` and `loop`
+/// statements, falling back to `goto` where the flow is not structured.
+struct Structurer<'a> {
+    blocks: &'a [BlockIr],
+    lines: Vec<Line>,
+    gotos: BTreeSet<usize>,
+}
+
+#[derive(Copy, Clone)]
+struct Loop {
+    header: usize,
+    exit: Option<usize>,
+}
+
+impl<'a> Structurer<'a> {
+    fn text(&mut self, depth: usize, text: String) {
+        self.lines.push(Line::Text(depth, text));
+    }
+
+    /// The address reached by leaving the block at `index` without jumping.
+    fn next(&self, index: usize, to: usize, follow: Option<usize>) -> Option<usize> {
+        if index + 1 < to {
+            Some(self.blocks[index + 1].start)
+        } else {
+            follow
+        }
+    }
+
+    /// The index of the block at `address` in `from..to`, or `to` if the
+    /// address is where control goes after the region.
+    fn find(&self, address: usize, from: usize, to: usize, follow: Option<usize>) -> Option<usize> {
+        (from..to).find(|index| self.blocks[*index].start == address)
+            .or_else(|| if follow == Some(address) { Some(to) } else { None })
+    }
+
+    /// How to get to `target` from the end of a block: nothing, `continue`,
+    /// `break` or a `goto`.
+    fn jump(&mut self, target: usize, next: Option<usize>, inner: Option<Loop>) -> Option<String> {
+        match inner {
+            _ if next == Some(target) => None,
+            Some(inner) if inner.header == target => Some(String::from("continue")),
+            Some(inner) if inner.exit == Some(target) => Some(String::from("break")),
+            _ => {
+                self.gotos.insert(target);
+                Some(format!("goto {}", label_name(target)))
+            }
+        }
+    }
+
+    fn emit(&mut self, from: usize, to: usize, depth: usize, follow: Option<usize>, inner: Option<Loop>, opened: Option<usize>) {
+        let blocks = self.blocks;
+        let mut index = from;
+
+        while index < to {
+            let block = &blocks[index];
+
+            if opened != Some(index) {
+                self.lines.push(Line::Label(block.start, depth));
+
+                let back = (index..to).rev().find(|k| blocks[*k].jumps_back_to(block.start));
+
+                if let Some(last) = back {
+                    let exit = self.next(last, to, follow);
+                    let body = Some(Loop { header: block.start, exit });
+
+                    match &block.term {
+                        Term::Branch(cond, target) if block.stmts.is_empty() && last > index && exit == Some(*target) => {
+                            self.text(depth, format!("while ({}) {{", cond.clone().negate()));
+                            self.emit(index + 1, last + 1, depth + 1, Some(block.start), body, None);
+                        }
+                        _ => {
+                            self.text(depth, String::from("loop {"));
+                            self.emit(index, last + 1, depth + 1, Some(block.start), body, Some(index));
+                        }
+                    }
+
+                    self.text(depth, String::from("}"));
+                    index = last + 1;
+                    continue;
+                }
+            }
+
+            for stmt in &block.stmts {
+                let text = match stmt {
+                    Stmt::Assign(place, value) => format!("{} = {}", place, value),
+                    Stmt::Output(value) => format!("output({})", value),
+                    Stmt::Print(text) => format!("print(\"{}\")", text),
+                    Stmt::MoveBase(offset) => format!("rb += {}", offset),
+                };
+
+                self.text(depth, text);
+            }
+
+            let next = self.next(index, to, follow);
+
+            match block.term.clone() {
+                Term::Fall(target) | Term::Goto(target) => {
+                    if let Some(text) = self.jump(target, next, inner) {
+                        self.text(depth, text);
+                    }
+                }
+                Term::Branch(cond, target) => {
+                    let forward = if target > block.start { self.find(target, index + 1, to, follow) } else { None };
+
+                    match (inner, forward) {
+                        (Some(inner), _) if inner.header == target || inner.exit == Some(target) => {
+                            let text = self.jump(target, None, Some(inner)).unwrap();
+                            self.text(depth, format!("if ({}) {}", cond, text));
+                        }
+                        (_, Some(join)) => {
+                            let join_start = if join < to { Some(blocks[join].start) } else { follow };
+                            let otherwise = match &blocks[join - 1].term {
+                                Term::Goto(end) if join - 1 > index && *end > target => {
+                                    self.find(*end, join, to, follow).map(|end_index| (*end, end_index))
+                                }
+                                _ => None,
+                            };
+
+                            self.text(depth, format!("if ({}) {{", cond.negate()));
+
+                            match otherwise {
+                                Some((end, end_index)) => {
+                                    self.emit(index + 1, join, depth + 1, Some(end), inner, None);
+                                    self.text(depth, String::from("} else {"));
+                                    self.emit(join, end_index, depth + 1, Some(end), inner, None);
+                                    self.text(depth, String::from("}"));
+                                    index = end_index;
+                                }
+                                None => {
+                                    self.emit(index + 1, join, depth + 1, join_start, inner, None);
+                                    self.text(depth, String::from("}"));
+                                    index = join;
+                                }
+                            }
+
+                            continue;
+                        }
+                        _ => {
+                            self.gotos.insert(target);
+                            self.text(depth, format!("if ({}) goto {}", cond, label_name(target)));
+                        }
+                    }
+                }
+                Term::Call(target, args) => {
+                    let args: Vec<String> = args.iter().map(Expr::to_string).collect();
+                    self.text(depth, format!("{}({})", function_name(target), args.join(", ")));
+                }
+                Term::Return(cond) => match cond {
+                    Some(cond) => self.text(depth, format!("if ({}) return", cond)),
+                    None => self.text(depth, String::from("return")),
+                },
+                Term::IndirectGoto(cond, target) => match cond {
+                    Some(cond) => self.text(depth, format!("if ({}) goto *{}", cond, target)),
+                    None => self.text(depth, format!("goto *{}", target)),
+                },
+                Term::Halt => self.text(depth, String::from("halt")),
+                Term::Invalid(address) => self.text(depth, format!("// runs into data at {}", address)),
+            }
+
+            index += 1;
+        }
+    }
+}
+
+/// Splits the graph into functions: the program entry and every call
+/// target, each with the blocks it reaches without following calls.
+fn functions(graph: &ControlFlowGraph) -> Vec<Function> {
+    let mut successors: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+
+    for edge in graph.edges() {
+        if edge.kind != EdgeKind::Call {
+            successors.entry(edge.from).or_default().push(edge.to);
+        }
+    }
+
+    let entries: BTreeSet<usize> = std::iter::once(0).chain(graph.functions().iter().copied()).collect();
+    let mut owned: BTreeSet<usize> = BTreeSet::new();
+    let mut functions = Vec::new();
+
+    for entry in &entries {
+        let mut blocks = Vec::new();
+        let mut queue = VecDeque::from(vec![*entry]);
+
+        while let Some(start) = queue.pop_front() {
+            if !graph.blocks().contains_key(&start) || owned.contains(&start) || (start != *entry && entries.contains(&start)) {
+                continue;
+            }
+
+            owned.insert(start);
+            blocks.push(start);
+            queue.extend(successors.get(&start).into_iter().flatten());
+        }
+
+        blocks.sort_unstable();
+
+        let frame = match graph.blocks().get(entry).and_then(|block| block.instructions.first()) {
+            Some(Instruction { op: Op::ModRel, operands, .. }) if *entry != 0 && operands[0].mode == AddressingMode::Immediate => {
+                operands[0].value.max(0)
+            }
+            _ => 0,
+        };
+
+        functions.push(Function { entry: *entry, frame, params: 0, blocks });
+    }
+
+    functions
+}
+
+/// Decompiles a program into C-like pseudocode, using the control-flow
+/// graph for structure, see `ControlFlowGraph`.
+///
+/// Each call target becomes a function. Relative addresses inside a
+/// function are named after its stack frame: `argN` and `localN` for the
+/// words of the frame, with as many arguments as the most any call passes,
+/// and `outN` for the words past the frame that hold the arguments of the
+/// next call and the values it leaves there. `mem[N]` is absolute memory,
+/// and `input()` and `output()` are the I/O instructions, with runs of
+/// ASCII output shown as `print`. Conditions computed into a temporary
+/// just before a jump are folded into the jump, and blocks are laid out as
+/// `if`, `while` and `loop` where the jumps allow, and with `goto`
+/// otherwise. Code that is never reached statically is left out.
+pub fn decompile(graph: &ControlFlowGraph) -> String {
+    let mut functions = functions(graph);
+
+    let written: BTreeSet<usize> = graph.blocks().values()
+        .flat_map(|block| &block.instructions)
+        .filter(|instruction| instruction.has_dest())
+        .map(|instruction| instruction.operands[instruction.operands.len() - 1])
+        .filter(|dest| dest.mode == AddressingMode::Absolute && dest.value >= 0)
+        .map(|dest| dest.value as usize)
+        .collect();
+    let mut params: BTreeMap<usize, usize> = BTreeMap::new();
+
+    for function in &functions {
+        for start in &function.blocks {
+            let block = &graph.blocks()[start];
+
+            if block.exit == Exit::Call {
+                let ir = convert(function, block, &written);
+
+                if let Term::Call(target, args) = ir.term {
+                    let count = params.entry(target).or_insert(0);
+                    *count = (*count).max(args.len());
+                }
+            }
+        }
+    }
+
+    for function in &mut functions {
+        function.params = params.get(&function.entry).copied().unwrap_or(0);
+        function.params = function.params.min((function.frame.max(1) - 1) as usize);
+    }
+
+    let mut out = String::new();
+
+    for function in &functions {
+        let blocks: Vec<BlockIr> = function.blocks.iter().map(|start| convert(function, &graph.blocks()[start], &written)).collect();
+        let mut structurer = Structurer { blocks: &blocks, lines: Vec::new(), gotos: BTreeSet::new() };
+        structurer.emit(0, blocks.len(), 1, None, None, None);
+
+        let params: Vec<String> = (1..=function.params).map(|param| format!("arg{}", param)).collect();
+        writeln!(out, "fn {}({}) {{", function.name(), params.join(", ")).unwrap();
+
+        for line in &structurer.lines {
+            match line {
+                Line::Label(address, depth) if structurer.gotos.contains(address) => {
+                    writeln!(out, "{}{}:", "    ".repeat(depth - 1), label_name(*address)).unwrap();
+                }
+                Line::Label(_, _) => (),
+                Line::Text(depth, text) => writeln!(out, "{}{}", "    ".repeat(*depth), text).unwrap(),
+            }
+        }
+
+        writeln!(out, "}}\n").unwrap();
+    }
+
+    out.pop();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assemble;
+
+    #[test]
+    fn test_decompile() {
+        let memory = assemble("
+                    REL #stack
+                    ADD #3 #0 -> [rb+1]
+                    ADD #back #0 -> [rb+0]
+                    JZ #0 #countdown
+            back:   ADD #list #0 -> [read+2]
+            read:   ADD #0 [0] -> [value]
+                    OUT [value]
+                    HALT
+            countdown:
+                    REL #3
+            loop:   JZ [rb-2] #done
+                    EQ [rb-2] #2 -> [rb-1]
+                    JZ [rb-1] #odd
+                    OUT #104
+                    OUT #105
+                    JZ #0 #next
+            odd:    OUT [rb-2]
+            next:   ADD [rb-2] #-1 -> [rb-2]
+                    JZ #0 #loop
+            done:   REL #-3
+                    JZ #0 [rb+0]
+            list:   .data 7
+            value:  .data 0
+            stack:  .data 0
+        ").unwrap();
+
+        let code = decompile(&ControlFlowGraph::new(&memory));
+
+        assert!(code.starts_with("fn main() {\n    rb += 59\n    f24(3)\n    mem[58] = mem[57]\n    output(mem[58])\n    halt\n}\n"), "{}", code);
+        assert!(code.ends_with("\
+fn f24(arg1) {
+    while (arg1 != 0) {
+        if (arg1 == 2) {
+            print(\"hi\")
+        } else {
+            output(arg1)
+        }
+        arg1 = arg1 - 1
+    }
+    return
+}
+"), "{}", code);
+    }
+
+    #[test]
+    fn test_decompile_extreme_constants() {
+        let memory = assemble("
+                    ADD [x] #-170141183460469231731687303715884105728 -> [x]
+                    ADD #back #0 -> [rb+0]
+                    JZ #0 #function
+            back:   HALT
+            function:
+                    REL #170141183460469231731687303715884105727
+                    OUT [rb+170141183460469231731687303715884105727]
+                    REL #-170141183460469231731687303715884105727
+                    JZ #0 [rb+0]
+            x:      .data 0
+        ").unwrap();
+
+        let code = decompile(&ControlFlowGraph::new(&memory));
+
+        assert!(code.contains("mem[21] = mem[21] + -170141183460469231731687303715884105728\n"), "{}", code);
+        assert!(code.contains("output(out170141183460469231731687303715884105727)\n"), "{}", code);
+    }
+}
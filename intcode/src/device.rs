@@ -0,0 +1,272 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::{Unit, Word};
+
+/// A source of input values. `Ok(None)` means no value is available yet,
+/// which blocks the computer until more input arrives.
+pub trait InputDevice<W> {
+    fn read(&mut self) -> io::Result<Option<W>>;
+}
+
+/// A sink for output values.
+pub trait OutputDevice<W> {
+    fn write(&mut self, value: W) -> io::Result<()>;
+}
+
+impl<W> InputDevice<W> for VecDeque<W> {
+    fn read(&mut self) -> io::Result<Option<W>> {
+        Ok(self.pop_front())
+    }
+}
+
+impl<W> OutputDevice<W> for VecDeque<W> {
+    fn write(&mut self, value: W) -> io::Result<()> {
+        self.push_back(value);
+        Ok(())
+    }
+}
+
+/// A queue with shared ownership, so one handle can be given to a computer
+/// while another is used to feed or drain it. Connecting the output of one
+/// computer to the input of another is just a matter of giving both a clone
+/// of the same queue.
+#[derive(Debug)]
+pub struct Queue<W> {
+    values: Arc<Mutex<VecDeque<W>>>,
+}
+
+impl<W> Queue<W> {
+    pub fn new() -> Queue<W> {
+        Queue { values: Arc::new(Mutex::new(VecDeque::new())) }
+    }
+
+    pub fn push(&self, value: W) {
+        self.values.lock().unwrap().push_back(value);
+    }
+
+    pub fn pop(&self) -> Option<W> {
+        self.values.lock().unwrap().pop_front()
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes and returns all queued values.
+    pub fn drain(&self) -> Vec<W> {
+        self.values.lock().unwrap().drain(..).collect()
+    }
+}
+
+impl<W> Clone for Queue<W> {
+    fn clone(&self) -> Queue<W> {
+        Queue { values: Arc::clone(&self.values) }
+    }
+}
+
+impl<W> Default for Queue<W> {
+    fn default() -> Queue<W> {
+        Queue::new()
+    }
+}
+
+impl<W> InputDevice<W> for Queue<W> {
+    fn read(&mut self) -> io::Result<Option<W>> {
+        Ok(self.pop())
+    }
+}
+
+impl<W> OutputDevice<W> for Queue<W> {
+    fn write(&mut self, value: W) -> io::Result<()> {
+        self.push(value);
+        Ok(())
+    }
+}
+
+/// Input produced by a closure.
+pub struct FnInput<F>(pub F);
+
+impl<W, F: FnMut() -> Option<W>> InputDevice<W> for FnInput<F> {
+    fn read(&mut self) -> io::Result<Option<W>> {
+        Ok((self.0)())
+    }
+}
+
+/// Output consumed by a closure.
+pub struct FnOutput<F>(pub F);
+
+impl<W, F: FnMut(W)> OutputDevice<W> for FnOutput<F> {
+    fn write(&mut self, value: W) -> io::Result<()> {
+        (self.0)(value);
+        Ok(())
+    }
+}
+
+/// Yields `default` whenever `device` has nothing to give, e.g. the `-1` a
+/// network interface reads when no packet is waiting.
+pub struct OrDefault<D, W> {
+    device: D,
+    default: W,
+}
+
+impl<D, W> OrDefault<D, W> {
+    pub fn new(device: D, default: W) -> OrDefault<D, W> {
+        OrDefault { device, default }
+    }
+}
+
+impl<W: Clone, D: InputDevice<W>> InputDevice<W> for OrDefault<D, W> {
+    fn read(&mut self) -> io::Result<Option<W>> {
+        Ok(Some(self.device.read()?.unwrap_or_else(|| self.default.clone())))
+    }
+}
+
+/// Reads stdin a line at a time and feeds it as ASCII, including the
+/// newline. Blocks the computer at end of file.
+#[derive(Debug, Default)]
+pub struct AsciiStdin {
+    buffer: VecDeque<u8>,
+}
+
+impl AsciiStdin {
+    pub fn new() -> AsciiStdin {
+        AsciiStdin::default()
+    }
+}
+
+impl<W: Word> InputDevice<W> for AsciiStdin {
+    fn read(&mut self) -> io::Result<Option<W>> {
+        if self.buffer.is_empty() {
+            let mut line = String::new();
+            io::stdin().read_line(&mut line)?;
+            self.buffer.extend(line.bytes());
+        }
+
+        Ok(self.buffer.pop_front().map(|c| W::from_unit(c as Unit)))
+    }
+}
+
+/// Prints ASCII output as text, and any value outside the ASCII range as a
+/// number on a line of its own.
+#[derive(Debug, Default)]
+pub struct AsciiStdout;
+
+impl<W: Word> OutputDevice<W> for AsciiStdout {
+    fn write(&mut self, value: W) -> io::Result<()> {
+        let mut stdout = io::stdout();
+
+        match value.to_unit() {
+            Some(c) if (0..128).contains(&c) => write!(stdout, "{}", c as u8 as char)?,
+            _ => writeln!(stdout, "{}", value)?,
+        }
+
+        if value.to_unit() == Some(10) {
+            stdout.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads integers separated by commas or whitespace, such as a file of
+/// inputs with one value per line.
+pub struct ReaderInput<R> {
+    reader: R,
+    buffer: VecDeque<String>,
+}
+
+impl<R: BufRead> ReaderInput<R> {
+    pub fn new(reader: R) -> ReaderInput<R> {
+        ReaderInput { reader, buffer: VecDeque::new() }
+    }
+}
+
+impl ReaderInput<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<ReaderInput<BufReader<File>>> {
+        Ok(ReaderInput::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<W: Word, R: BufRead> InputDevice<W> for ReaderInput<R> {
+    fn read(&mut self) -> io::Result<Option<W>> {
+        while self.buffer.is_empty() {
+            let mut line = String::new();
+
+            if self.reader.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+
+            self.buffer.extend(
+                line.split(|c: char| c == ',' || c.is_whitespace())
+                    .filter(|s| !s.is_empty())
+                    .map(String::from)
+            );
+        }
+
+        let value = self.buffer.pop_front().unwrap();
+
+        value.parse().map(Some).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("Invalid input value: {}", value))
+        })
+    }
+}
+
+/// Writes each output value on a line of its own.
+pub struct WriterOutput<T> {
+    writer: T,
+}
+
+impl<T: Write> WriterOutput<T> {
+    pub fn new(writer: T) -> WriterOutput<T> {
+        WriterOutput { writer }
+    }
+}
+
+impl WriterOutput<BufWriter<File>> {
+    pub fn create(path: impl AsRef<Path>) -> io::Result<WriterOutput<BufWriter<File>>> {
+        Ok(WriterOutput::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Word, T: Write> OutputDevice<W> for WriterOutput<T> {
+    fn write(&mut self, value: W) -> io::Result<()> {
+        writeln!(self.writer, "{}", value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Computer, State};
+
+    #[test]
+    fn test_devices() {
+        // Outputs each input value doubled, until it reads a 0
+        let memory = vec![3, 15, 1006, 15, 14, 1002, 15, 2, 15, 4, 15, 1105, 1, 0, 99, 0];
+        let pipe = Queue::new();
+
+        let mut first = Computer::with_devices(&memory, ReaderInput::new("1, 2\n3\n0".as_bytes()), pipe.clone());
+        assert_eq!(first.run(), Ok(State::Halted));
+        pipe.push(0);
+
+        let collected = Queue::new();
+        let sink = collected.clone();
+        let mut second = Computer::with_devices(&memory, pipe, FnOutput(move |value| sink.push(value)));
+        assert_eq!(second.run(), Ok(State::Halted));
+        assert_eq!(collected.drain(), vec![4, 8, 12]);
+    }
+
+    #[test]
+    fn test_or_default() {
+        let mut device = OrDefault::new(FnInput(|| None), -1);
+        assert_eq!(device.read().unwrap(), Some(-1));
+    }
+}
@@ -0,0 +1,289 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::{Instruction, Memory, Unit};
+
+const DATA_WORDS_PER_LINE: usize = 8;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Entry {
+    Instruction(Instruction),
+    Data(usize, Unit),
+}
+
+impl Entry {
+    pub fn address(&self) -> usize {
+        match self {
+            Entry::Instruction(instruction) => instruction.address,
+            Entry::Data(address, _) => *address,
+        }
+    }
+}
+
+/// An annotated disassembly of a memory image.
+///
+/// Every word of the disassembled segments is covered by exactly one entry,
+/// so assembling the rendered listing of a contiguous program, as given to
+/// `disassemble`, reproduces it word for word. The listing of a gapped
+/// memory from `disassemble_memory` has no gaps, so it assembles to the
+/// segments back to back.
+#[derive(Debug, Clone)]
+pub struct Listing {
+    entries: Vec<Entry>,
+    labels: BTreeSet<usize>,
+    notes: BTreeMap<usize, String>,
+}
+
+pub fn label_name(address: usize) -> String {
+    format!("L{}", address)
+}
+
+fn jump_targets<'a>(instructions: impl Iterator<Item = &'a Instruction>) -> BTreeSet<usize> {
+    instructions
+        .filter(|instruction| instruction.may_jump())
+        .filter_map(Instruction::jump_target)
+        .collect()
+}
+
+/// Sweeps each `(start address, words)` segment in turn. Instructions do not
+/// run on from one segment into the next.
+fn sweep(segments: &[(usize, &[Unit])], barriers: &BTreeSet<usize>, notes: &mut BTreeMap<usize, String>) -> Vec<Entry> {
+    let mut entries = Vec::new();
+
+    for (start, words) in segments {
+        let mut offset = 0;
+
+        while offset < words.len() {
+            let address = start + offset;
+            let entry = match Instruction::decode(words, offset) {
+                Some(instruction) if !instruction.is_canonical() => {
+                    notes.insert(address, format!("ambiguous: decodes as {} with unused mode digits", instruction));
+                    Entry::Data(address, words[offset])
+                }
+                Some(instruction) => {
+                    let end = address + instruction.size();
+
+                    match barriers.range(address + 1..end).next() {
+                        Some(target) => {
+                            notes.insert(address, format!("ambiguous: decodes as {} but overlaps {}", instruction, label_name(*target)));
+                            Entry::Data(address, words[offset])
+                        }
+                        None => Entry::Instruction(Instruction { address, ..instruction }),
+                    }
+                }
+                None => Entry::Data(address, words[offset]),
+            };
+
+            offset += match &entry {
+                Entry::Instruction(instruction) => instruction.size(),
+                Entry::Data(_, _) => 1,
+            };
+
+            entries.push(entry);
+        }
+    }
+
+    entries
+}
+
+/// Disassembles a memory image, such as a program as loaded by
+/// `read_memory`.
+///
+/// Words are decoded by a linear sweep from address 0. Statically known jump
+/// targets become labels, and a word that would decode into an instruction
+/// straddling a label is kept as data. Words that do not decode, or only
+/// decode with redundant mode digits, are shown as `.data`.
+pub fn disassemble(memory: &[Unit]) -> Listing {
+    disassemble_segments(&[(0, memory)])
+}
+
+/// Like `disassemble`, but for the memory of a running computer, such as
+/// `Computer::get_memory()`. Only the allocated `segments` are swept, so
+/// memory written far out is cheap. Gaps between segments are left out of
+/// the listing.
+pub fn disassemble_memory(memory: &Memory) -> Listing {
+    let segments = memory.segments();
+    let segments: Vec<(usize, &[Unit])> = segments.iter().map(|(start, words)| (*start, words.as_slice())).collect();
+
+    disassemble_segments(&segments)
+}
+
+fn disassemble_segments(segments: &[(usize, &[Unit])]) -> Listing {
+    let mut notes = BTreeMap::new();
+
+    let first_pass = sweep(segments, &BTreeSet::new(), &mut notes);
+    let barriers = jump_targets(first_pass.iter().filter_map(|entry| match entry {
+        Entry::Instruction(instruction) => Some(instruction),
+        Entry::Data(_, _) => None,
+    }));
+
+    notes.clear();
+    let entries = sweep(segments, &barriers, &mut notes);
+
+    let starts: BTreeSet<usize> = entries.iter().map(Entry::address).collect();
+    let instructions: Vec<&Instruction> = entries.iter()
+        .filter_map(|entry| match entry {
+            Entry::Instruction(instruction) => Some(instruction),
+            Entry::Data(_, _) => None,
+        })
+        .collect();
+
+    let mut labels = BTreeSet::new();
+
+    for instruction in &instructions {
+        if let Some(target) = instruction.jump_target().filter(|_| instruction.may_jump()) {
+            if starts.contains(&target) {
+                labels.insert(target);
+            } else if segments.iter().any(|(start, words)| (*start..start + words.len()).contains(&target)) {
+                notes.insert(instruction.address, format!("ambiguous: jumps into the middle of an entry at {}", target));
+            }
+        }
+    }
+
+    let mut reachable = true;
+
+    for entry in &entries {
+        if let Entry::Instruction(instruction) = entry {
+            if !reachable && !labels.contains(&instruction.address) {
+                notes.entry(instruction.address)
+                    .or_insert_with(|| String::from("ambiguous: not reached by fallthrough or direct jump"));
+            }
+
+            reachable = instruction.falls_through();
+        } else {
+            reachable = false;
+        }
+    }
+
+    Listing { entries, labels, notes }
+}
+
+impl Listing {
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    pub fn labels(&self) -> &BTreeSet<usize> {
+        &self.labels
+    }
+
+    /// Annotations for entries that may not be what they seem, keyed by address.
+    pub fn notes(&self) -> &BTreeMap<usize, String> {
+        &self.notes
+    }
+
+    fn format_instruction(&self, instruction: &Instruction) -> String {
+        match instruction.jump_target() {
+            Some(target) if self.labels.contains(&target) => {
+                format!("{} {} #{}", instruction.op.mnemonic(), instruction.operands[0], label_name(target))
+            }
+            _ => instruction.to_string(),
+        }
+    }
+}
+
+fn write_line(f: &mut fmt::Formatter, address: usize, text: &str, note: Option<&String>) -> fmt::Result {
+    match note {
+        Some(note) => writeln!(f, "{:>6}  {:<36} ; {}", address, text, note),
+        None => writeln!(f, "{:>6}  {}", address, text),
+    }
+}
+
+impl fmt::Display for Listing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut data: Vec<(usize, Unit)> = Vec::new();
+
+        let flush = |f: &mut fmt::Formatter, data: &mut Vec<(usize, Unit)>| -> fmt::Result {
+            if let Some((address, _)) = data.first() {
+                let values = data.iter()
+                    .map(|(_, value)| value.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                write_line(f, *address, &format!(".data {}", values), None)?;
+            }
+
+            data.clear();
+            Ok(())
+        };
+
+        for entry in &self.entries {
+            let address = entry.address();
+            let note = self.notes.get(&address);
+
+            if self.labels.contains(&address) || note.is_some() || data.len() == DATA_WORDS_PER_LINE {
+                flush(f, &mut data)?;
+            }
+
+            if self.labels.contains(&address) {
+                writeln!(f, "{}:", label_name(address))?;
+            }
+
+            match entry {
+                Entry::Instruction(instruction) => {
+                    flush(f, &mut data)?;
+                    write_line(f, address, &self.format_instruction(instruction), note)?;
+                }
+                Entry::Data(_, value) if note.is_some() => {
+                    write_line(f, address, &format!(".data {}", value), note)?;
+                }
+                Entry::Data(_, value) => data.push((address, *value)),
+            }
+        }
+
+        flush(f, &mut data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assemble;
+
+    #[test]
+    fn test_listing() {
+        let memory = vec![1005, 9, 7, 204, -3, 99, 42, 1101, 5, 6, 10];
+        let expected = concat!(
+            "     0  JNZ [9] #L7\n",
+            "     3  OUT [rb-3]\n",
+            "     5  HALT\n",
+            "     6  .data 42\n",
+            "L7:\n",
+            "     7  ADD #5 #6 -> [10]\n",
+        );
+
+        assert_eq!(disassemble(&memory).to_string(), expected);
+    }
+
+    #[test]
+    fn test_ambiguous_words() {
+        let listing = disassemble(&[1105, 1, 4, 104, 99, 10004, 1, 99]);
+
+        assert_eq!(listing.entries()[1], Entry::Data(3, 104));
+        assert!(listing.notes()[&3].contains("overlaps L4"));
+        assert!(listing.notes()[&5].contains("unused mode digits"));
+        assert!(listing.notes()[&7].contains("not reached"));
+    }
+
+    #[test]
+    fn test_sparse_memory() {
+        let mut memory: Memory = Memory::new(4, 64);
+        memory.load(0, &[1105, 1, 1_000_000_000_000, 99]).unwrap();
+        memory.load(1_000_000_000_000, &[104, 7, 99]).unwrap();
+
+        let listing = disassemble_memory(&memory);
+
+        assert_eq!(listing.entries()[2].address(), 1_000_000_000_000);
+        assert!(listing.labels().contains(&1_000_000_000_000));
+        assert!(listing.to_string().contains("L1000000000000:\n1000000000000  OUT #7\n"));
+    }
+
+    #[test]
+    fn test_gapped_round_trip() {
+        let mut memory: Memory = Memory::new(4, 64);
+        memory.load(0, &[104, 1, 99]).unwrap();
+        memory.load(8, &[104, 2, 99]).unwrap();
+
+        assert_eq!(assemble(&disassemble(&memory.to_vec()).to_string()), Ok(memory.to_vec()));
+        assert_eq!(assemble(&disassemble_memory(&memory).to_string()), Ok(vec![104, 1, 99, 0, 104, 2, 99]));
+    }
+}
@@ -0,0 +1,82 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::{Op, Unit};
+
+/// A fault raised while executing an Intcode program. Every variant records
+/// the pc of the faulting instruction and the raw opcode found there.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum IntcodeError {
+    UnknownOpcode { pc: usize, opcode: Unit },
+    UnknownAddressingMode { pc: usize, opcode: Unit, position: usize },
+    NegativeAddress { pc: usize, opcode: Unit, address: Unit },
+    ImmediateDestination { pc: usize, opcode: Unit, position: usize },
+    HaltedComputer { pc: usize, opcode: Unit },
+    MemoryLimitExceeded { pc: usize, opcode: Unit, address: Unit },
+    Overflow { pc: usize, opcode: Unit, left: Unit, right: Unit },
+    Device { pc: usize, opcode: Unit, message: String },
+}
+
+impl IntcodeError {
+    pub fn pc(&self) -> usize {
+        match *self {
+            IntcodeError::UnknownOpcode { pc, .. }
+            | IntcodeError::UnknownAddressingMode { pc, .. }
+            | IntcodeError::NegativeAddress { pc, .. }
+            | IntcodeError::ImmediateDestination { pc, .. }
+            | IntcodeError::HaltedComputer { pc, .. }
+            | IntcodeError::MemoryLimitExceeded { pc, .. }
+            | IntcodeError::Overflow { pc, .. }
+            | IntcodeError::Device { pc, .. } => pc,
+        }
+    }
+
+    pub fn opcode(&self) -> Unit {
+        match *self {
+            IntcodeError::UnknownOpcode { opcode, .. }
+            | IntcodeError::UnknownAddressingMode { opcode, .. }
+            | IntcodeError::NegativeAddress { opcode, .. }
+            | IntcodeError::ImmediateDestination { opcode, .. }
+            | IntcodeError::HaltedComputer { opcode, .. }
+            | IntcodeError::MemoryLimitExceeded { opcode, .. }
+            | IntcodeError::Overflow { opcode, .. }
+            | IntcodeError::Device { opcode, .. } => opcode,
+        }
+    }
+}
+
+impl fmt::Display for IntcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}] ", self.pc())?;
+
+        match self {
+            IntcodeError::UnknownOpcode { opcode, .. } => {
+                write!(f, "Unknown opcode: {}", opcode)
+            }
+            IntcodeError::UnknownAddressingMode { opcode, position, .. } => {
+                write!(f, "Unknown addressing mode for parameter {} of {}", position, opcode)
+            }
+            IntcodeError::NegativeAddress { address, .. } => {
+                write!(f, "Address less than zero: {}", address)
+            }
+            IntcodeError::ImmediateDestination { opcode, position, .. } => {
+                write!(f, "Destination cannot be immediate: parameter {} of {}", position, opcode)
+            }
+            IntcodeError::HaltedComputer { .. } => {
+                write!(f, "Cannot start halted computer")
+            }
+            IntcodeError::MemoryLimitExceeded { address, .. } => {
+                write!(f, "Memory limit exceeded writing to address {}", address)
+            }
+            IntcodeError::Overflow { opcode, left, right, .. } => {
+                let op = Op::decode(*opcode).map_or("?", |op| op.mnemonic());
+                write!(f, "Arithmetic overflow: {} {} {}", op, left, right)
+            }
+            IntcodeError::Device { message, .. } => {
+                write!(f, "Device error: {}", message)
+            }
+        }
+    }
+}
+
+impl Error for IntcodeError {}
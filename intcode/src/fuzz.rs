@@ -0,0 +1,493 @@
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::{disassemble, AddressingMode, Computer, Instruction, IntcodeError, Op, Operand, State, Unit};
+
+use Op::*;
+
+/// Steps a generated program can take at most is its length, since every
+/// jump goes forward, so this only guards against a broken implementation.
+const BUDGET: u64 = 1_000_000;
+
+/// An operand of a generated instruction. Reads and writes go to the data
+/// after the code, and jumps to an instruction further down, so every
+/// program terminates.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Arg {
+    Const(Unit),
+    /// A data cell, by index.
+    Data(usize),
+    /// An instruction, by index, where one past the last is the final HALT.
+    Target(usize),
+}
+
+/// A generated program: instructions, a HALT, then the data cells. `input`
+/// has a value for every IN so that no implementation blocks.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Program {
+    pub instructions: Vec<(Op, Vec<Arg>)>,
+    pub data: Vec<Unit>,
+    pub input: Vec<Unit>,
+}
+
+impl Program {
+    pub fn to_memory(&self) -> Vec<Unit> {
+        let mut addresses = vec![0];
+
+        for (op, _) in &self.instructions {
+            addresses.push(addresses[addresses.len() - 1] + op.num_params().0 + 1);
+        }
+
+        let data = addresses[self.instructions.len()] + 1;
+
+        let mut memory: Vec<Unit> = self.instructions.iter()
+            .zip(&addresses)
+            .flat_map(|((op, args), address)| {
+                let operands = args.iter()
+                    .map(|arg| match arg {
+                        Arg::Const(value) => Operand { mode: AddressingMode::Immediate, value: *value },
+                        Arg::Data(index) => Operand { mode: AddressingMode::Absolute, value: (data + index) as Unit },
+                        Arg::Target(index) => Operand { mode: AddressingMode::Immediate, value: addresses[*index] as Unit },
+                    })
+                    .collect();
+
+                Instruction::from_operands(*address, *op, operands).encode()
+            })
+            .collect();
+
+        memory.push(Halt.code());
+        memory.extend(&self.data);
+        memory
+    }
+
+    /// Whether every word of the program and its input, and every result of
+    /// its arithmetic, fits in the `i32` of the standalone VMs of days 5 and
+    /// 7, so that they can only differ from a wider VM through a bug.
+    pub fn fits_i32(&self) -> bool {
+        let to_i32 = |values: &[Unit]| values.iter().map(|value| i32::try_from(*value).ok()).collect::<Option<Vec<i32>>>();
+
+        let (memory, input) = match (to_i32(&self.to_memory()), to_i32(&self.input)) {
+            (Some(memory), Some(input)) => (memory, input),
+            _ => return false,
+        };
+
+        let mut computer = Computer::new(&memory, Some(&input));
+        !matches!(computer.run_for(BUDGET), Err(IntcodeError::Overflow { .. }))
+    }
+
+    /// Smaller variants of the program, most promising first: without an
+    /// instruction, an unused data cell or unused input, with an operand,
+    /// data value or input closer to 0, or with a constant in place of a
+    /// read.
+    fn candidates(&self) -> Vec<Program> {
+        let mut candidates = Vec::new();
+
+        for i in 0..self.instructions.len() {
+            let mut program = self.clone();
+            program.instructions.remove(i);
+
+            for (_, args) in &mut program.instructions {
+                for arg in args {
+                    if let Arg::Target(target) = arg {
+                        if *target > i {
+                            *target -= 1;
+                        }
+                    }
+                }
+            }
+
+            candidates.push(program);
+        }
+
+        let used: BTreeSet<usize> = self.instructions.iter()
+            .flat_map(|(_, args)| args)
+            .filter_map(|arg| if let Arg::Data(index) = arg { Some(*index) } else { None })
+            .collect();
+
+        if let Some(unused) = (0..self.data.len()).find(|index| !used.contains(index)) {
+            let mut program = self.clone();
+            program.data.remove(unused);
+
+            for (_, args) in &mut program.instructions {
+                for arg in args {
+                    if let Arg::Data(index) = arg {
+                        if *index > unused {
+                            *index -= 1;
+                        }
+                    }
+                }
+            }
+
+            candidates.push(program);
+        }
+
+        let inputs = self.instructions.iter().filter(|(op, _)| *op == Input).count();
+
+        if inputs < self.input.len() {
+            let mut program = self.clone();
+            program.input.truncate(inputs);
+            candidates.push(program);
+        }
+
+        for (i, (op, args)) in self.instructions.iter().enumerate() {
+            let (num_params, has_dest) = op.num_params();
+
+            for (position, arg) in args.iter().enumerate() {
+                let replacements = match arg {
+                    Arg::Const(value) => smaller(*value).into_iter().map(Arg::Const).collect(),
+                    Arg::Data(_) if !(has_dest && position == num_params - 1) => vec![Arg::Const(0)],
+                    _ => Vec::new(),
+                };
+
+                for replacement in replacements {
+                    let mut program = self.clone();
+                    program.instructions[i].1[position] = replacement;
+                    candidates.push(program);
+                }
+            }
+        }
+
+        for (i, value) in self.data.iter().enumerate() {
+            for value in smaller(*value) {
+                let mut program = self.clone();
+                program.data[i] = value;
+                candidates.push(program);
+            }
+        }
+
+        for (i, value) in self.input.iter().enumerate() {
+            for value in smaller(*value) {
+                let mut program = self.clone();
+                program.input[i] = value;
+                candidates.push(program);
+            }
+        }
+
+        candidates
+    }
+}
+
+fn smaller(value: Unit) -> Vec<Unit> {
+    match value {
+        0 => vec![],
+        1 | -1 => vec![0],
+        _ => vec![0, value / 2],
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let memory: Vec<String> = self.to_memory().iter().map(Unit::to_string).collect();
+        let input: Vec<String> = self.input.iter().map(Unit::to_string).collect();
+
+        writeln!(f, "Program: {}", memory.join(","))?;
+        writeln!(f, "Input: {}", input.join(","))?;
+        write!(f, "{}", disassemble(&self.to_memory()))
+    }
+}
+
+/// A small xorshift generator, so that a seed always gives the same program.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        let mut rng = Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1);
+        rng.next();
+        rng
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn value(&mut self, max: Unit) -> Unit {
+        (self.next() % (2 * max as u64 + 1)) as Unit - max
+    }
+}
+
+/// Generates programs from the opcodes every implementation has: no
+/// relative mode and no REL.
+///
+/// Programs are kept within `i32`, see `Program::fits_i32`: a seed gives the
+/// first program drawn from it that fits, so that `i32` VMs can be compared
+/// with wider ones without reporting overflow as a divergence.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Generator {
+    pub instructions: usize,
+    pub data: usize,
+    /// Constants, data and input are drawn from `-max_value..=max_value`,
+    /// with `max_value` capped at `i32::MAX`.
+    pub max_value: Unit,
+}
+
+impl Default for Generator {
+    fn default() -> Generator {
+        Generator { instructions: 20, data: 8, max_value: 50 }
+    }
+}
+
+impl Generator {
+    const OPS: [Op; 8] = [Add, Mul, Input, Output, JumpNZ, JumpZ, LessThan, Equals];
+
+    pub fn generate(&self, seed: u64) -> Program {
+        let mut rng = Rng::new(seed);
+
+        loop {
+            let program = self.draw(&mut rng);
+
+            if program.fits_i32() {
+                return program;
+            }
+        }
+    }
+
+    fn draw(&self, rng: &mut Rng) -> Program {
+        let max_value = self.max_value.clamp(0, i32::MAX as Unit);
+        let length = self.instructions;
+        let mut instructions = Vec::new();
+
+        // Without data words there is nowhere to write, so only the ops
+        // without a destination remain
+        let ops: Vec<Op> = Generator::OPS.iter().copied().filter(|op| self.data > 0 || !op.num_params().1).collect();
+
+        for i in 0..length {
+            let op = if rng.below(20) == 0 { Halt } else { ops[rng.below(ops.len())] };
+            let (num_params, has_dest) = op.num_params();
+
+            let args = (0..num_params)
+                .map(|position| match op {
+                    JumpNZ | JumpZ if position == 1 => Arg::Target(i + 1 + rng.below(length - i)),
+                    _ if has_dest && position == num_params - 1 => Arg::Data(rng.below(self.data)),
+                    _ if self.data > 0 && rng.below(2) == 0 => Arg::Data(rng.below(self.data)),
+                    _ => Arg::Const(rng.value(max_value)),
+                })
+                .collect();
+
+            instructions.push((op, args));
+        }
+
+        let inputs = instructions.iter().filter(|(op, _)| *op == Input).count();
+
+        Program {
+            instructions,
+            data: (0..self.data).map(|_| rng.value(max_value)).collect(),
+            input: (0..inputs).map(|_| rng.value(max_value)).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Termination {
+    Halted,
+    Blocked,
+    /// An error or panic, whose message is not compared.
+    Failed(String),
+}
+
+/// What running a program on one implementation led to.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Outcome {
+    pub output: Vec<Unit>,
+    pub memory: Vec<Unit>,
+    pub termination: Termination,
+}
+
+impl Outcome {
+    fn failed(message: String) -> Outcome {
+        Outcome { output: Vec::new(), memory: Vec::new(), termination: Termination::Failed(message) }
+    }
+
+    /// Two failures agree whatever they left behind, anything else has to
+    /// match exactly.
+    pub fn agrees(&self, other: &Outcome) -> bool {
+        match (&self.termination, &other.termination) {
+            (Termination::Failed(_), Termination::Failed(_)) => true,
+            _ => self == other,
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let join = |values: &[Unit]| values.iter().map(Unit::to_string).collect::<Vec<String>>().join(",");
+
+        match &self.termination {
+            Termination::Failed(message) => write!(f, "failed: {}", message),
+            termination => write!(f, "{:?} with output [{}] and memory [{}]", termination, join(&self.output), join(&self.memory)),
+        }
+    }
+}
+
+/// Runs a program on this crate's `Computer`.
+pub fn run_computer(memory: &[Unit], input: &[Unit]) -> Outcome {
+    let mut computer = Computer::new(&memory.to_vec(), Some(&input.to_vec()));
+
+    let termination = match computer.run_for(BUDGET) {
+        Ok(State::Halted) => Termination::Halted,
+        Ok(State::Blocked) => Termination::Blocked,
+        Ok(state) => Termination::Failed(format!("stopped {:?}", state)),
+        Err(e) => return Outcome::failed(e.to_string()),
+    };
+
+    Outcome { output: computer.get_output().iter().copied().collect(), memory: computer.get_memory().to_vec(), termination }
+}
+
+/// A program, shrunk as far as it still diverges, and what each
+/// implementation made of it.
+#[derive(Debug, Clone)]
+pub struct Divergence {
+    pub seed: u64,
+    pub program: Program,
+    pub outcomes: Vec<(String, Outcome)>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Divergence for seed {}", self.seed)?;
+        write!(f, "{}", self.program)?;
+
+        for (name, outcome) in &self.outcomes {
+            writeln!(f, "{}: {}", name, outcome)?;
+        }
+
+        Ok(())
+    }
+}
+
+type Implementation = Box<dyn Fn(&[Unit], &[Unit]) -> Outcome>;
+
+/// Runs generated programs on several Intcode implementations and compares
+/// their outcomes with those of the first. Implementations may panic, which
+/// counts as failing and is not printed.
+pub struct Fuzzer {
+    generator: Generator,
+    implementations: Vec<(String, Implementation)>,
+}
+
+impl Fuzzer {
+    pub fn new(generator: Generator) -> Fuzzer {
+        Fuzzer { generator, implementations: Vec::new() }
+    }
+
+    /// Adds an implementation, a function from program and input to outcome.
+    pub fn add(&mut self, name: &str, implementation: impl Fn(&[Unit], &[Unit]) -> Outcome + 'static) {
+        self.implementations.push((name.to_string(), Box::new(implementation)));
+    }
+
+    fn outcomes(&self, program: &Program) -> Vec<(String, Outcome)> {
+        let memory = program.to_memory();
+
+        self.implementations.iter()
+            .map(|(name, implementation)| {
+                // Panics are expected and reported as failures, so only this
+                // call runs with a silent panic hook
+                let hook = panic::take_hook();
+                panic::set_hook(Box::new(|_| ()));
+                let result = panic::catch_unwind(AssertUnwindSafe(|| implementation(&memory, &program.input)));
+                panic::set_hook(hook);
+
+                let outcome = result.unwrap_or_else(|payload| {
+                    let message = payload.downcast_ref::<&str>().map(|message| message.to_string())
+                        .or_else(|| payload.downcast_ref::<String>().cloned())
+                        .unwrap_or_else(|| String::from("panicked"));
+
+                    Outcome::failed(message)
+                });
+
+                (name.clone(), outcome)
+            })
+            .collect()
+    }
+
+    fn diverges(&self, program: &Program) -> bool {
+        let outcomes = self.outcomes(program);
+        outcomes.iter().any(|(_, outcome)| !outcome.agrees(&outcomes[0].1))
+    }
+
+    /// Shrinks a diverging program by taking the first smaller variant that
+    /// still fits in `i32` and diverges, until none does.
+    pub fn shrink(&self, program: &Program) -> Program {
+        let mut program = program.clone();
+
+        while let Some(smaller) = program.candidates().into_iter().find(|candidate| candidate.fits_i32() && self.diverges(candidate)) {
+            program = smaller;
+        }
+
+        program
+    }
+
+    /// Checks the program generated from each seed, and returns the first
+    /// divergence shrunk to a minimal reproducer.
+    pub fn run(&self, mut seeds: Range<u64>) -> Option<Divergence> {
+        let seed = seeds.find(|seed| self.diverges(&self.generator.generate(*seed)))?;
+        let program = self.shrink(&self.generator.generate(seed));
+        let outcomes = self.outcomes(&program);
+
+        Some(Divergence { seed, program, outcomes })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzer() {
+        let generator = Generator::default();
+        let program = generator.generate(7);
+
+        assert_eq!(program, generator.generate(7));
+        assert_eq!(run_computer(&program.to_memory(), &program.input).termination, Termination::Halted);
+
+        let mut fuzzer = Fuzzer::new(generator);
+        fuzzer.add("computer", run_computer);
+        fuzzer.add("again", run_computer);
+
+        assert!(fuzzer.run(0..100).is_none());
+
+        // Differs from the computer on any output of a negative number
+        fuzzer.add("unsigned", |memory, input| {
+            let outcome = run_computer(memory, input);
+            assert!(outcome.output.iter().all(|value| *value >= 0), "negative output");
+            outcome
+        });
+
+        let divergence = fuzzer.run(0..100).unwrap();
+
+        assert_eq!(divergence.program.instructions, vec![(Input, vec![Arg::Data(0)]), (Output, vec![Arg::Data(0)])]);
+        assert_eq!(divergence.program.to_memory(), vec![3, 5, 4, 5, 99, 0]);
+        assert_eq!(divergence.program.input, vec![-1]);
+        assert_eq!(divergence.outcomes[2].1, Outcome::failed(String::from("negative output")));
+        assert!(divergence.to_string().contains("unsigned: failed: negative output\n"));
+    }
+
+    #[test]
+    fn test_programs_fit_i32() {
+        let overflowing = Program { instructions: vec![(Mul, vec![Arg::Const(65536), Arg::Const(65536), Arg::Data(0)])], data: vec![0], input: vec![] };
+        assert!(!overflowing.fits_i32());
+
+        let generator = Generator { max_value: Unit::MAX, ..Generator::default() };
+        assert!((0..100).all(|seed| generator.generate(seed).fits_i32()));
+    }
+
+    #[test]
+    fn test_generator_without_data() {
+        let generator = Generator { data: 0, ..Generator::default() };
+
+        for seed in 0..100 {
+            let program = generator.generate(seed);
+            assert!(program.data.is_empty());
+            assert_eq!(run_computer(&program.to_memory(), &program.input).termination, Termination::Halted);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::VecDeque;
+
+use crate::{MemoryWrite, State, Unit};
+
+/// What it takes to undo one executed instruction: the registers and state
+/// before it ran, the memory write it made, and the values it took from the
+/// input queue or appended to the output queue. `step` counts like
+/// `TraceRecord::step`, from 1.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct UndoRecord<W = Unit> {
+    pub step: u64,
+    pub pc: usize,
+    pub relative_base: Unit,
+    pub state: State,
+    pub write: Option<MemoryWrite<W>>,
+    pub input: Option<W>,
+    pub output: Option<W>,
+}
+
+/// The undo log of a computer, newest record last. With a limit it is a
+/// ring buffer that forgets the oldest records, so a long run can be
+/// rewound by at most `limit` steps.
+#[derive(Debug, Clone)]
+pub struct History<W = Unit> {
+    records: VecDeque<UndoRecord<W>>,
+    limit: Option<usize>,
+    forgotten: u64,
+}
+
+impl<W> History<W> {
+    pub fn new(limit: Option<usize>) -> History<W> {
+        History { records: VecDeque::new(), limit, forgotten: 0 }
+    }
+
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// The number of records dropped to stay within the limit.
+    pub fn forgotten(&self) -> u64 {
+        self.forgotten
+    }
+
+    pub fn records(&self) -> impl DoubleEndedIterator<Item = &UndoRecord<W>> {
+        self.records.iter()
+    }
+
+    /// The most recent instruction still in the log that wrote to `address`.
+    pub fn last_write(&self, address: usize) -> Option<&UndoRecord<W>> {
+        self.records.iter()
+            .rev()
+            .find(|record| matches!(&record.write, Some(write) if write.address == address))
+    }
+
+    pub(crate) fn push(&mut self, record: UndoRecord<W>) {
+        if self.limit == Some(0) {
+            self.forgotten += 1;
+            return;
+        }
+
+        if Some(self.records.len()) == self.limit {
+            self.records.pop_front();
+            self.forgotten += 1;
+        }
+
+        self.records.push_back(record);
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<UndoRecord<W>> {
+        self.records.pop_back()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(step: u64, write: Option<usize>) -> UndoRecord {
+        let write = write.map(|address| MemoryWrite { address, old: 0, new: step as Unit });
+        UndoRecord { step, pc: 0, relative_base: 0, state: State::Running, write, input: None, output: None }
+    }
+
+    #[test]
+    fn test_ring_buffer() {
+        let mut history = History::new(Some(2));
+        history.push(record(1, Some(10)));
+        history.push(record(2, Some(11)));
+        history.push(record(3, None));
+
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.forgotten(), 1);
+        assert_eq!(history.last_write(11).map(|record| record.step), Some(2));
+        assert_eq!(history.last_write(10), None);
+        assert_eq!(history.pop().map(|record| record.step), Some(3));
+    }
+}
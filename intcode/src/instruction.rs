@@ -0,0 +1,150 @@
+use std::fmt;
+
+use crate::{get_addressing_mode, AddressingMode, Op, Unit};
+
+use AddressingMode::{Absolute, Immediate, Relative};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Operand {
+    pub mode: AddressingMode,
+    pub value: Unit,
+}
+
+impl Operand {
+    fn mode_digit(&self) -> Unit {
+        match self.mode {
+            Absolute => 0,
+            Immediate => 1,
+            Relative => 2,
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.mode {
+            Absolute => write!(f, "[{}]", self.value),
+            Immediate => write!(f, "#{}", self.value),
+            Relative if self.value < 0 => write!(f, "[rb{}]", self.value),
+            Relative => write!(f, "[rb+{}]", self.value),
+        }
+    }
+}
+
+/// A single decoded instruction together with the address it was decoded at.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Instruction {
+    pub address: usize,
+    pub opcode: Unit,
+    pub op: Op,
+    pub operands: Vec<Operand>,
+}
+
+impl Instruction {
+    /// Decodes the instruction at `address`, or returns `None` if the words
+    /// there cannot be executed: unknown opcode or addressing mode, an
+    /// immediate destination, or parameters running past the end of memory.
+    pub fn decode(memory: &[Unit], address: usize) -> Option<Instruction> {
+        let opcode = *memory.get(address)?;
+
+        if opcode < 0 {
+            return None;
+        }
+
+        let op = Op::decode(opcode).ok()?;
+        let (num_params, has_dest) = op.num_params();
+
+        let operands = (0..num_params)
+            .map(|position| {
+                let mode = get_addressing_mode(opcode, position)?;
+                let value = *memory.get(address + position + 1)?;
+
+                match (mode, has_dest && position == num_params - 1) {
+                    (Immediate, true) => None,
+                    _ => Some(Operand { mode, value }),
+                }
+            })
+            .collect::<Option<Vec<Operand>>>()?;
+
+        Some(Instruction { address, opcode, op, operands })
+    }
+
+    pub fn from_operands(address: usize, op: Op, operands: Vec<Operand>) -> Instruction {
+        let opcode = operands.iter()
+            .enumerate()
+            .fold(op.code(), |opcode, (position, operand)| {
+                opcode + operand.mode_digit() * (10 as Unit).pow(position as u32 + 2)
+            });
+
+        Instruction { address, opcode, op, operands }
+    }
+
+    /// The number of memory words the instruction occupies.
+    pub fn size(&self) -> usize {
+        self.operands.len() + 1
+    }
+
+    pub fn encode(&self) -> Vec<Unit> {
+        let mut words = vec![self.opcode];
+        words.extend(self.operands.iter().map(|operand| operand.value));
+        words
+    }
+
+    /// Whether the opcode is the shortest encoding of this instruction, i.e.
+    /// it has no mode digits beyond those of its parameters.
+    pub fn is_canonical(&self) -> bool {
+        Instruction::from_operands(self.address, self.op, self.operands.clone()).opcode == self.opcode
+    }
+
+    pub fn has_dest(&self) -> bool {
+        self.op.num_params().1
+    }
+
+    /// The target of a jump whose destination is known statically.
+    pub fn jump_target(&self) -> Option<usize> {
+        match self.op {
+            Op::JumpNZ | Op::JumpZ => match self.operands[1] {
+                Operand { mode: Immediate, value } if value >= 0 => Some(value as usize),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Whether execution may continue with the instruction directly after
+    /// this one.
+    pub fn falls_through(&self) -> bool {
+        match (self.op, self.operands.first()) {
+            (Op::Halt, _) => false,
+            (Op::JumpNZ, Some(Operand { mode: Immediate, value })) => *value == 0,
+            (Op::JumpZ, Some(Operand { mode: Immediate, value })) => *value != 0,
+            _ => true,
+        }
+    }
+
+    /// Whether the conditional jump can ever be taken.
+    pub fn may_jump(&self) -> bool {
+        match (self.op, self.operands.first()) {
+            (Op::JumpNZ, Some(Operand { mode: Immediate, value })) => *value != 0,
+            (Op::JumpZ, Some(Operand { mode: Immediate, value })) => *value == 0,
+            (Op::JumpNZ, _) | (Op::JumpZ, _) => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.op.mnemonic())?;
+
+        for (position, operand) in self.operands.iter().enumerate() {
+            if self.has_dest() && position == self.operands.len() - 1 {
+                write!(f, " -> {}", operand)?;
+            } else {
+                write!(f, " {}", operand)?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,50 @@
+mod assembler;
+mod cfg;
+mod code;
+mod computer;
+mod conformance;
+mod debugger;
+mod decoded;
+mod decompiler;
+mod device;
+mod disassembler;
+mod error;
+mod fuzz;
+mod history;
+mod instruction;
+mod memory;
+mod network;
+mod op;
+mod profiler;
+mod runtime;
+mod terminal;
+mod topology;
+mod trace;
+mod watch;
+mod word;
+
+pub use assembler::{assemble, AssemblerError};
+pub use cfg::{observed_jumps, Block, ControlFlowGraph, Edge, EdgeKind, Exit};
+pub use code::{CodeRole, CodeTracker, CodeWrite};
+pub use conformance::{conformance, Feature, Vm};
+pub use computer::{get_addressing_mode, parse_memory, parse_words, read_memory, AddressingMode, Computer, State, Unit};
+pub use debugger::{Debugger, Stop};
+pub use decompiler::decompile;
+pub use device::{AsciiStdin, AsciiStdout, FnInput, FnOutput, InputDevice, OrDefault, OutputDevice, Queue, ReaderInput, WriterOutput};
+pub use disassembler::{disassemble, disassemble_memory, label_name, Entry, Listing};
+pub use error::IntcodeError;
+pub use fuzz::{run_computer, Arg, Divergence, Fuzzer, Generator, Outcome, Program, Termination};
+pub use history::{History, UndoRecord};
+pub use instruction::{Instruction, Operand};
+pub use memory::{Memory, MemoryLimitExceeded};
+pub use network::{Addressing, NatAction, NatEvent, Network, NetworkStatus, Packet};
+pub use op::Op;
+pub use profiler::Profile;
+pub use runtime::{Runtime, RuntimeError};
+pub use terminal::{AsciiTerminal, Newline};
+pub use topology::Topology;
+pub use trace::{parse_trace, read_program_and_trace, MemoryWrite, TraceRecord, TraceSink};
+pub use watch::{Access, WatchAction, WatchEvent};
+pub use word::{Arithmetic, Word};
+
+pub use num_bigint::BigInt;
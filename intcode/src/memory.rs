@@ -0,0 +1,332 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Index;
+use std::sync::Arc;
+
+use crate::{MemoryWrite, Unit, Word};
+
+/// Pages with an index below this live in a directly indexed table, the
+/// rest in an ordered map so that far away addresses stay cheap.
+const DENSE_PAGES: usize = 1 << 16;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct MemoryLimitExceeded {
+    pub address: usize,
+    pub limit: usize,
+}
+
+/// Sparse, paged Intcode memory.
+///
+/// Pages are allocated on first write, and reading an unallocated address
+/// yields 0 without allocating anything. Pages are shared copy-on-write, so
+/// cloning a `Memory` only copies page pointers. The number of allocated
+/// words is capped by `limit`.
+#[derive(Debug, Clone)]
+pub struct Memory<W = Unit> {
+    page_shift: u32,
+    limit: usize,
+    len: usize,
+    num_pages: usize,
+    dense: Vec<Option<Arc<Vec<W>>>>,
+    sparse: BTreeMap<usize, Arc<Vec<W>>>,
+    zero: W,
+}
+
+impl Memory {
+    pub const DEFAULT_PAGE_SIZE: usize = 1024;
+    pub const DEFAULT_LIMIT: usize = 1 << 26;
+    /// The largest page size a save file may ask for.
+    pub const MAX_PAGE_SIZE: usize = 1 << 20;
+}
+
+impl<W: Word> Memory<W> {
+    /// Creates an empty memory. `page_size` must be a power of two, and
+    /// `limit` is the maximum number of words that may be allocated.
+    pub fn new(page_size: usize, limit: usize) -> Memory<W> {
+        assert!(page_size.is_power_of_two(), "Page size must be a power of two: {}", page_size);
+
+        Memory {
+            page_shift: page_size.trailing_zeros(),
+            limit,
+            len: 0,
+            num_pages: 0,
+            dense: Vec::new(),
+            sparse: BTreeMap::new(),
+            zero: W::default(),
+        }
+    }
+
+    pub fn page_size(&self) -> usize {
+        1 << self.page_shift
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// One past the highest address written so far.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of words currently backed by allocated pages.
+    pub fn allocated(&self) -> usize {
+        self.num_pages * self.page_size()
+    }
+
+    fn page(&self, index: usize) -> Option<&Arc<Vec<W>>> {
+        if index < DENSE_PAGES {
+            self.dense.get(index).and_then(Option::as_ref)
+        } else {
+            self.sparse.get(&index)
+        }
+    }
+
+    fn pages(&self) -> impl Iterator<Item = (usize, &Arc<Vec<W>>)> {
+        self.dense.iter()
+            .enumerate()
+            .filter_map(|(index, page)| page.as_ref().map(|page| (index, page)))
+            .chain(self.sparse.iter().map(|(index, page)| (*index, page)))
+    }
+
+    pub fn get(&self, address: usize) -> W {
+        self[address].clone()
+    }
+
+    pub fn set(&mut self, address: usize, value: W) -> Result<(), MemoryLimitExceeded> {
+        let end = address.checked_add(1).ok_or(MemoryLimitExceeded { address, limit: self.limit })?;
+        let index = address >> self.page_shift;
+        let offset = address & (self.page_size() - 1);
+
+        if self.page(index).is_none() {
+            if value == self.zero {
+                self.len = self.len.max(end);
+                return Ok(());
+            }
+
+            if self.allocated() + self.page_size() > self.limit {
+                return Err(MemoryLimitExceeded { address, limit: self.limit });
+            }
+
+            self.num_pages += 1;
+        }
+
+        let page_size = self.page_size();
+        let page = if index < DENSE_PAGES {
+            if index >= self.dense.len() {
+                self.dense.resize(index + 1, None);
+            }
+
+            self.dense[index].get_or_insert_with(|| Arc::new(vec![W::default(); page_size]))
+        } else {
+            self.sparse.entry(index).or_insert_with(|| Arc::new(vec![W::default(); page_size]))
+        };
+
+        Arc::make_mut(page)[offset] = value;
+        self.len = self.len.max(end);
+
+        Ok(())
+    }
+
+    /// Writes `values` to consecutive addresses starting at `address`. Running
+    /// past the end of the address space counts as exceeding the limit.
+    pub fn load(&mut self, address: usize, values: &[W]) -> Result<(), MemoryLimitExceeded> {
+        let end = address.checked_add(values.len()).ok_or(MemoryLimitExceeded { address, limit: self.limit })?;
+
+        values.iter()
+            .enumerate()
+            .try_for_each(|(offset, value)| self.set(address + offset, value.clone()))?;
+
+        self.len = self.len.max(end);
+        Ok(())
+    }
+
+    /// A dense copy of addresses `0..len()`. This takes `len()` words even if
+    /// little of it is allocated, so prefer `segments` for memory that may
+    /// have been written far out.
+    pub fn to_vec(&self) -> Vec<W> {
+        let mut words = Vec::with_capacity(self.len);
+
+        for (start, segment) in self.segments() {
+            words.resize(start, W::default());
+            words.extend(segment);
+        }
+
+        words.resize(self.len, W::default());
+        words
+    }
+
+    /// Runs of allocated memory as `(start address, words)`, with adjacent
+    /// pages merged and trailing words beyond `len()` left out.
+    pub fn segments(&self) -> Vec<(usize, Vec<W>)> {
+        let mut segments: Vec<(usize, Vec<W>)> = Vec::new();
+
+        for (index, page) in self.pages() {
+            let start = index << self.page_shift;
+            let end = start.saturating_add(self.page_size()).min(self.len);
+
+            if start >= end {
+                continue;
+            }
+
+            match segments.last_mut() {
+                Some((segment_start, words)) if *segment_start + words.len() == start => {
+                    words.extend_from_slice(&page[..end - start]);
+                }
+                _ => segments.push((start, page[..end - start].to_vec())),
+            }
+        }
+
+        segments
+    }
+
+    /// True if every allocated word here has the same value in `other`.
+    /// Pages shared with `other` are skipped.
+    fn covered_by(&self, other: &Memory<W>) -> bool {
+        self.pages().all(|(index, page)| {
+            let start = index << self.page_shift;
+
+            match other.page(start >> other.page_shift) {
+                Some(theirs) if self.page_shift == other.page_shift && Arc::ptr_eq(page, theirs) => true,
+                _ => page.iter().enumerate().all(|(offset, value)| *value == other[start + offset]),
+            }
+        })
+    }
+
+    /// The addresses whose value differs in `other`, typically a later clone
+    /// of this memory, with the value here as `old` and there as `new`. Pages
+    /// the two still share are skipped without being compared.
+    pub fn diff(&self, other: &Memory<W>) -> Vec<MemoryWrite<W>> {
+        assert_eq!(self.page_shift, other.page_shift, "Cannot compare memories with different page sizes");
+
+        let indices: BTreeSet<usize> = self.pages().chain(other.pages()).map(|(index, _)| index).collect();
+        let mut changes = Vec::new();
+
+        for index in indices {
+            if let (Some(a), Some(b)) = (self.page(index), other.page(index)) {
+                if Arc::ptr_eq(a, b) {
+                    continue;
+                }
+            }
+
+            let start = index << self.page_shift;
+
+            for address in start..start.saturating_add(self.page_size()) {
+                if self[address] != other[address] {
+                    changes.push(MemoryWrite { address, old: self.get(address), new: other.get(address) });
+                }
+            }
+        }
+
+        changes
+    }
+}
+
+impl<W: Word> Default for Memory<W> {
+    fn default() -> Memory<W> {
+        Memory::new(Memory::DEFAULT_PAGE_SIZE, Memory::DEFAULT_LIMIT)
+    }
+}
+
+impl<W: Word> From<&[W]> for Memory<W> {
+    fn from(program: &[W]) -> Memory<W> {
+        let limit = Memory::DEFAULT_LIMIT.max(program.len() + Memory::DEFAULT_PAGE_SIZE);
+        let mut memory = Memory::new(Memory::DEFAULT_PAGE_SIZE, limit);
+        memory.load(0, program).expect("Limit leaves room for the program");
+        memory
+    }
+}
+
+impl<W: Word> Index<usize> for Memory<W> {
+    type Output = W;
+
+    fn index(&self, address: usize) -> &W {
+        match self.page(address >> self.page_shift) {
+            Some(page) => &page[address & (self.page_size() - 1)],
+            None => &self.zero,
+        }
+    }
+}
+
+impl<W: Word> PartialEq for Memory<W> {
+    fn eq(&self, other: &Memory<W>) -> bool {
+        self.len == other.len && self.covered_by(other) && other.covered_by(self)
+    }
+}
+
+impl<W: Word> Eq for Memory<W> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_far_addresses_are_cheap() {
+        let mut memory: Memory = Memory::new(16, 64);
+        memory.set(1_000_000_000_000, 7).unwrap();
+
+        assert_eq!(memory.get(1_000_000_000_000), 7);
+        assert_eq!(memory[999_999_999_999], 0);
+        assert_eq!(memory.allocated(), 16);
+        assert_eq!(memory.len(), 1_000_000_000_001);
+
+        let mut other: Memory = Memory::new(64, 128);
+        other.set(1_000_000_000_000, 7).unwrap();
+        assert_eq!(memory, other);
+
+        other.set(5, 1).unwrap();
+        assert_ne!(memory, other);
+    }
+
+    #[test]
+    fn test_limit() {
+        let mut memory: Memory = Memory::new(16, 32);
+        memory.set(0, 1).unwrap();
+        memory.set(100, 1).unwrap();
+
+        assert_eq!(memory.set(200, 1), Err(MemoryLimitExceeded { address: 200, limit: 32 }));
+        assert_eq!(memory.set(5, 1), Ok(()));
+    }
+
+    #[test]
+    fn test_clones_share_pages() {
+        let mut memory: Memory = Memory::new(4, 64);
+        memory.load(0, &[1, 2, 3, 4, 5, 6]).unwrap();
+
+        let mut clone = memory.clone();
+        assert_eq!(memory, clone);
+        clone.set(1, 20).unwrap();
+
+        assert_eq!(memory.to_vec(), vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(clone.to_vec(), vec![1, 20, 3, 4, 5, 6]);
+        assert!(Arc::ptr_eq(memory.page(1).unwrap(), clone.page(1).unwrap()));
+    }
+
+    #[test]
+    fn test_segments() {
+        let mut memory: Memory = Memory::new(4, 64);
+        memory.load(0, &[1, 2, 3, 4, 5]).unwrap();
+        memory.set(100, 9).unwrap();
+
+        assert_eq!(memory.segments(), vec![(0, vec![1, 2, 3, 4, 5, 0, 0, 0]), (100, vec![9])]);
+    }
+
+    #[test]
+    fn test_diff() {
+        let mut memory: Memory = Memory::new(4, 64);
+        memory.load(0, &[1, 2, 3, 4, 5]).unwrap();
+
+        let mut later = memory.clone();
+        later.set(4, 50).unwrap();
+        later.set(100, 9).unwrap();
+
+        assert_eq!(memory.diff(&later), vec![
+            MemoryWrite { address: 4, old: 5, new: 50 },
+            MemoryWrite { address: 100, old: 0, new: 9 },
+        ]);
+        assert_eq!(later.diff(&memory)[0], MemoryWrite { address: 4, old: 50, new: 5 });
+    }
+}
@@ -0,0 +1,402 @@
+use crate::{Computer, RuntimeError, State, Unit, Word};
+
+/// A packet between two network addresses, see `Addressing`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Packet<W = Unit> {
+    pub source: Unit,
+    pub destination: Unit,
+    pub values: Vec<W>,
+}
+
+/// How nodes are addressed. Node `i` has address `first_address + i` and is
+/// given its address as its first input. Every packet is an address followed
+/// by `payload` values.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Addressing {
+    pub first_address: Unit,
+    pub payload: usize,
+}
+
+impl Default for Addressing {
+    fn default() -> Addressing {
+        Addressing { first_address: 0, payload: 2 }
+    }
+}
+
+/// A snapshot of the network at the start of a round, given to the idle
+/// detector.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct NetworkStatus {
+    pub round: u64,
+    /// Output words produced in the last round and not yet routed.
+    pub pending_output: usize,
+    /// Input words delivered to nodes that have not halted but not yet read.
+    pub queued_input: usize,
+}
+
+pub enum NatEvent<'e, W> {
+    /// A packet was sent to the NAT address.
+    Packet(&'e Packet<W>),
+    /// The idle detector found the network idle.
+    Idle,
+}
+
+pub enum NatAction<W> {
+    Continue,
+    /// Delivers a packet to the node with its destination address.
+    Send(Packet<W>),
+    /// Stops the network, with the packet to return from `run`.
+    Stop(Packet<W>),
+}
+
+type NatPolicy<'a, W> = Box<dyn FnMut(NatEvent<W>) -> NatAction<W> + 'a>;
+type PacketHook<'a, W> = Box<dyn FnMut(&Packet<W>) + 'a>;
+
+/// Simulates nodes running the same or different programs, exchanging
+/// packets by address.
+///
+/// Scheduling is deterministic: every round visits the nodes in order, and a
+/// node first has its output routed, then gets the idle input if it has
+/// nothing to read, and then runs until it blocks. Before each round the idle
+/// detector is asked whether the network is idle, in which case the NAT is
+/// told so. By default the network is idle when the last round produced no
+/// output and no node has a packet waiting to be read.
+pub struct Network<'a, W = Unit> {
+    nodes: Vec<Computer<W>>,
+    addressing: Addressing,
+    idle_input: W,
+    idle_detector: Box<dyn FnMut(&NetworkStatus) -> bool + 'a>,
+    nat: Option<(Unit, NatPolicy<'a, W>)>,
+    hooks: Vec<PacketHook<'a, W>>,
+}
+
+impl<'a, W: Word> Network<'a, W> {
+    /// Creates `nodes` nodes running `memory`, using the default addressing
+    /// and `-1` as the idle input.
+    pub fn new(memory: &Vec<W>, nodes: usize) -> Network<'a, W> {
+        Network::with_computers((0..nodes).map(|_| Computer::new(memory, None)).collect())
+    }
+
+    pub fn with_computers(nodes: Vec<Computer<W>>) -> Network<'a, W> {
+        Network {
+            nodes,
+            addressing: Addressing::default(),
+            idle_input: W::from_unit(-1),
+            idle_detector: Box::new(|status| status.pending_output == 0 && status.queued_input == 0),
+            nat: None,
+            hooks: Vec::new(),
+        }
+    }
+
+    pub fn set_addressing(&mut self, addressing: Addressing) {
+        self.addressing = addressing;
+    }
+
+    /// The value a node reads when no packet is waiting for it.
+    pub fn set_idle_input(&mut self, value: W) {
+        self.idle_input = value;
+    }
+
+    pub fn set_idle_detector(&mut self, detector: impl FnMut(&NetworkStatus) -> bool + 'a) {
+        self.idle_detector = Box::new(detector);
+    }
+
+    /// Installs a NAT at `address`. Without a NAT, packets to addresses that
+    /// are not nodes are dropped. A NAT sending to `address` itself makes
+    /// `run` fail with `RuntimeError::NatSendToSelf`.
+    pub fn set_nat(&mut self, address: Unit, policy: impl FnMut(NatEvent<W>) -> NatAction<W> + 'a) {
+        self.nat = Some((address, Box::new(policy)));
+    }
+
+    /// Calls `hook` for every packet sent, including packets to the NAT,
+    /// packets sent by the NAT and packets that are dropped.
+    pub fn add_hook(&mut self, hook: impl FnMut(&Packet<W>) + 'a) {
+        self.hooks.push(Box::new(hook));
+    }
+
+    pub fn nodes(&self) -> &[Computer<W>] {
+        &self.nodes
+    }
+
+    fn node_index(&self, address: Unit) -> Option<usize> {
+        let index = address.checked_sub(self.addressing.first_address)?;
+
+        if (0..self.nodes.len() as Unit).contains(&index) {
+            Some(index as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Delivers a packet, returning what the NAT made of it if it was sent
+    /// to the NAT.
+    fn deliver(&mut self, packet: Packet<W>) -> NatAction<W> {
+        self.hooks.iter_mut().for_each(|hook| hook(&packet));
+
+        if let Some(index) = self.node_index(packet.destination) {
+            packet.values.into_iter().for_each(|value| self.nodes[index].push_input(value));
+            return NatAction::Continue;
+        }
+
+        match &mut self.nat {
+            Some((address, policy)) if *address == packet.destination => policy(NatEvent::Packet(&packet)),
+            _ => NatAction::Continue,
+        }
+    }
+
+    /// Routes the complete packets in the output queue of node `index`, and
+    /// returns the packet to stop at if the NAT says so.
+    fn route(&mut self, index: usize) -> Result<Option<Packet<W>>, RuntimeError<W>> {
+        let packet_len = self.addressing.payload + 1;
+
+        while self.nodes[index].get_output().len() >= packet_len {
+            let destination = self.nodes[index].pop_output().unwrap().to_unit_saturating();
+            let values = (0..self.addressing.payload).map(|_| self.nodes[index].pop_output().unwrap()).collect();
+
+            let source = self.addressing.first_address + index as Unit;
+
+            if let Some(packet) = self.act(Packet { source, destination, values })? {
+                return Ok(Some(packet));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Delivers a packet and whatever the NAT sends in response.
+    fn act(&mut self, packet: Packet<W>) -> Result<Option<Packet<W>>, RuntimeError<W>> {
+        let mut packet = packet;
+
+        loop {
+            match self.deliver(packet) {
+                NatAction::Continue => return Ok(None),
+                NatAction::Send(sent) => packet = self.check_nat_send(sent)?,
+                NatAction::Stop(packet) => return Ok(Some(packet)),
+            }
+        }
+    }
+
+    fn check_nat_send(&self, packet: Packet<W>) -> Result<Packet<W>, RuntimeError<W>> {
+        match &self.nat {
+            Some((address, _)) if *address == packet.destination => Err(RuntimeError::NatSendToSelf { address: *address }),
+            _ => Ok(packet),
+        }
+    }
+
+    fn status(&self, round: u64) -> NetworkStatus {
+        NetworkStatus {
+            round,
+            pending_output: self.nodes.iter().map(|node| node.get_output().len()).sum(),
+            queued_input: self.nodes.iter()
+                .filter(|node| *node.get_state() != State::Halted)
+                .map(|node| node.get_input().len())
+                .sum(),
+        }
+    }
+
+    /// Boots every node with its address and runs the network until the NAT
+    /// stops it, returning the packet it stopped at, or until every node has
+    /// halted.
+    pub fn run(&mut self) -> Result<Option<Packet<W>>, RuntimeError<W>> {
+        for (index, node) in self.nodes.iter_mut().enumerate() {
+            node.push_input(W::from_unit(self.addressing.first_address + index as Unit));
+            node.run().map_err(|error| RuntimeError::Machine { id: index, error })?;
+        }
+
+        for round in 1.. {
+            let status = self.status(round);
+
+            if (self.idle_detector)(&status) {
+                let action = match &mut self.nat {
+                    Some((_, policy)) => policy(NatEvent::Idle),
+                    None => NatAction::Continue,
+                };
+
+                let stop = match action {
+                    NatAction::Continue => None,
+                    NatAction::Send(packet) => self.act(self.check_nat_send(packet)?)?,
+                    NatAction::Stop(packet) => Some(packet),
+                };
+
+                if stop.is_some() {
+                    return Ok(stop);
+                }
+            }
+
+            if self.nodes.iter().all(|node| *node.get_state() == State::Halted) {
+                // Halted nodes may still have sent packets in their last run
+                for index in 0..self.nodes.len() {
+                    if let Some(packet) = self.route(index)? {
+                        return Ok(Some(packet));
+                    }
+                }
+
+                break;
+            }
+
+            for index in 0..self.nodes.len() {
+                if let Some(packet) = self.route(index)? {
+                    return Ok(Some(packet));
+                }
+
+                if *self.nodes[index].get_state() == State::Halted {
+                    continue;
+                }
+
+                if self.nodes[index].get_input().is_empty() {
+                    let idle_input = self.idle_input.clone();
+                    self.nodes[index].push_input(idle_input);
+                }
+
+                self.nodes[index].run().map_err(|error| RuntimeError::Machine { id: index, error })?;
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assemble;
+
+    #[test]
+    fn test_network() {
+        // Node 0 sends (7, 42) to node 1, which forwards (x, y + 1) to 255
+        let memory = assemble("
+                IN -> [addr]
+                JNZ [addr] #receive
+                OUT #1
+                OUT #7
+                OUT #42
+            idle:
+                IN -> [x]
+                JZ #0 #idle
+            receive:
+                IN -> [x]
+                EQ [x] #-1 -> [t]
+                JNZ [t] #receive
+                IN -> [y]
+                ADD [y] #1 -> [y]
+                OUT #255
+                OUT [x]
+                OUT [y]
+                JZ #0 #receive
+            addr: .data 0
+            x: .data 0
+            y: .data 0
+            t: .data 0
+        ").unwrap();
+
+        let mut packets = Vec::new();
+        let mut network = Network::new(&memory, 2);
+        network.add_hook(|packet| packets.push(packet.clone()));
+        network.set_nat(255, |event| match event {
+            NatEvent::Packet(packet) => NatAction::Stop(packet.clone()),
+            NatEvent::Idle => NatAction::Continue,
+        });
+
+        let stopped = network.run().unwrap();
+        drop(network);
+
+        assert_eq!(packets, vec![
+            Packet { source: 0, destination: 1, values: vec![7, 42] },
+            Packet { source: 1, destination: 255, values: vec![7, 43] },
+        ]);
+        assert_eq!(stopped.as_ref(), packets.last());
+    }
+
+    #[test]
+    fn test_network_is_not_idle_with_queued_packets() {
+        // Node 1 sends (7, 8) back to node 0, which forwards it to 255
+        let memory = assemble("
+                IN -> [addr]
+                JZ [addr] #receive
+                OUT #0
+                OUT #7
+                OUT #8
+            idle:
+                IN -> [x]
+                JZ #0 #idle
+            receive:
+                IN -> [x]
+                EQ [x] #-1 -> [t]
+                JNZ [t] #receive
+                IN -> [y]
+                OUT #255
+                OUT [x]
+                OUT [y]
+                JZ #0 #receive
+            addr: .data 0
+            x: .data 0
+            y: .data 0
+            t: .data 0
+        ").unwrap();
+
+        let mut events = Vec::new();
+        let mut network = Network::new(&memory, 2);
+        network.set_nat(255, |event| match event {
+            NatEvent::Packet(packet) => {
+                events.push(format!("packet {:?}", packet.values));
+                NatAction::Stop(packet.clone())
+            }
+            NatEvent::Idle => {
+                events.push(String::from("idle"));
+                NatAction::Continue
+            }
+        });
+
+        network.run().unwrap();
+        drop(network);
+
+        assert_eq!(events, vec!["packet [7, 8]"]);
+    }
+
+    #[test]
+    fn test_nat_cannot_send_to_itself() {
+        let memory = assemble("
+                IN -> [addr]
+                OUT #255
+                OUT #1
+                OUT #2
+                HALT
+            addr: .data 0
+        ").unwrap();
+
+        let mut network = Network::new(&memory, 1);
+        network.set_nat(255, |event| match event {
+            NatEvent::Packet(packet) => NatAction::Send(packet.clone()),
+            NatEvent::Idle => NatAction::Continue,
+        });
+
+        assert!(matches!(network.run(), Err(RuntimeError::NatSendToSelf { address: 255 })));
+    }
+
+    #[test]
+    fn test_network_routes_output_of_halted_nodes() {
+        let memory = assemble("
+                IN -> [addr]
+                OUT #255
+                OUT #1
+                OUT #2
+                HALT
+            addr: .data 0
+        ").unwrap();
+
+        let mut packets = Vec::new();
+        let mut network = Network::new(&memory, 1);
+        network.add_hook(|packet| packets.push(packet.clone()));
+        network.set_nat(255, |event| match event {
+            NatEvent::Packet(packet) => NatAction::Stop(packet.clone()),
+            NatEvent::Idle => NatAction::Continue,
+        });
+
+        let stopped = network.run().unwrap();
+        drop(network);
+
+        let expected = Packet { source: 0, destination: 255, values: vec![1, 2] };
+        assert_eq!(packets, vec![expected.clone()]);
+        assert_eq!(stopped, Some(expected));
+    }
+}
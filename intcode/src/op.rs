@@ -0,0 +1,87 @@
+use crate::Unit;
+
+use Op::*;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Op {
+    Add,
+    Mul,
+    Input,
+    Output,
+    JumpNZ,
+    JumpZ,
+    LessThan,
+    Equals,
+    ModRel,
+    Halt,
+}
+
+impl Op {
+    pub const ALL: [Op; 10] = [Add, Mul, Input, Output, JumpNZ, JumpZ, LessThan, Equals, ModRel, Halt];
+
+    pub fn decode(opcode: Unit) -> Result<Op, String> {
+        match opcode % 100 {
+            1 => Ok(Add),
+            2 => Ok(Mul),
+            3 => Ok(Input),
+            4 => Ok(Output),
+            5 => Ok(JumpNZ),
+            6 => Ok(JumpZ),
+            7 => Ok(LessThan),
+            8 => Ok(Equals),
+            9 => Ok(ModRel),
+            99 => Ok(Halt),
+            x => Err(format!("Unknown opcode: {}", x))
+        }
+    }
+
+    /// The number of parameters, and whether the last one is a destination.
+    pub fn num_params(&self) -> (usize, bool) {
+        match self {
+            Add | Mul => (3, true),
+            Input => (1, true),
+            Output => (1, false),
+            JumpNZ | JumpZ => (2, false),
+            LessThan | Equals => (3, true),
+            ModRel => (1, false),
+            Halt => (0, false)
+        }
+    }
+
+    /// The opcode of this operation without any addressing mode digits.
+    pub fn code(&self) -> Unit {
+        match self {
+            Add => 1,
+            Mul => 2,
+            Input => 3,
+            Output => 4,
+            JumpNZ => 5,
+            JumpZ => 6,
+            LessThan => 7,
+            Equals => 8,
+            ModRel => 9,
+            Halt => 99,
+        }
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Add => "ADD",
+            Mul => "MUL",
+            Input => "IN",
+            Output => "OUT",
+            JumpNZ => "JNZ",
+            JumpZ => "JZ",
+            LessThan => "LT",
+            Equals => "EQ",
+            ModRel => "REL",
+            Halt => "HALT",
+        }
+    }
+
+    pub fn from_mnemonic(mnemonic: &str) -> Option<Op> {
+        Op::ALL.iter()
+            .find(|op| op.mnemonic().eq_ignore_ascii_case(mnemonic))
+            .copied()
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::{label_name, AddressingMode, Instruction, Memory, Op, Unit, Word};
+
+/// Execution counts collected while a computer runs with profiling enabled.
+///
+/// Memory heat counts parameter reads and writes by address, but not the
+/// fetching of instructions themselves.
+///
+/// The folded stacks are a heuristic, as Intcode has no real calls: a `REL`
+/// that raises the relative base opens a frame named after the most recent
+/// jump target, which is where compiled Intcode enters a function, and one
+/// that lowers it closes the frames above the new base.
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    pub steps: u64,
+    pub pcs: HashMap<usize, u64>,
+    pub ops: HashMap<Op, u64>,
+    pub modes: HashMap<AddressingMode, u64>,
+    pub reads: HashMap<usize, u64>,
+    pub writes: HashMap<usize, u64>,
+    stacks: HashMap<Vec<usize>, u64>,
+    bases: Vec<Unit>,
+    entries: Vec<usize>,
+    last_target: usize,
+}
+
+fn sorted<K: Copy + Ord>(counts: &HashMap<K, u64>) -> Vec<(K, u64)> {
+    let mut counts: Vec<(K, u64)> = counts.iter().map(|(key, count)| (*key, *count)).collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    counts
+}
+
+impl Profile {
+    pub fn new() -> Profile {
+        Profile::default()
+    }
+
+    pub(crate) fn read(&mut self, address: usize) {
+        *self.reads.entry(address).or_insert(0) += 1;
+    }
+
+    pub(crate) fn write(&mut self, address: usize) {
+        *self.writes.entry(address).or_insert(0) += 1;
+    }
+
+    /// Records an executed instruction. `jump` is the new pc if the
+    /// instruction jumped, and `relative_base` the base before and after.
+    pub(crate) fn instruction(
+        &mut self,
+        pc: usize,
+        op: Op,
+        modes: &[Option<AddressingMode>],
+        jump: Option<usize>,
+        relative_base: (Unit, Unit),
+    ) {
+        self.steps += 1;
+        *self.pcs.entry(pc).or_insert(0) += 1;
+        *self.ops.entry(op).or_insert(0) += 1;
+
+        for mode in modes.iter().flatten() {
+            *self.modes.entry(*mode).or_insert(0) += 1;
+        }
+
+        match self.stacks.get_mut(&self.entries) {
+            Some(count) => *count += 1,
+            None => { self.stacks.insert(self.entries.clone(), 1); }
+        }
+
+        if let Some(target) = jump {
+            self.last_target = target;
+        }
+
+        let (before, after) = relative_base;
+
+        if after > before {
+            self.bases.push(after);
+            self.entries.push(self.last_target);
+        } else if after < before {
+            while self.bases.last().is_some_and(|base| *base > after) {
+                self.bases.pop();
+                self.entries.pop();
+            }
+        }
+    }
+
+    /// Call stacks with the number of instructions executed in each, one
+    /// `frame;frame count` line per stack, as read by flame graph tools.
+    pub fn folded(&self) -> String {
+        let mut lines: Vec<String> = self.stacks.iter()
+            .map(|(stack, count)| {
+                let frames: Vec<String> = std::iter::once(0).chain(stack.iter().copied()).map(label_name).collect();
+                format!("{} {}", frames.join(";"), count)
+            })
+            .collect();
+
+        lines.sort();
+        lines.iter().map(|line| format!("{}\n", line)).collect()
+    }
+
+    /// A report of the `limit` hottest instructions and memory addresses,
+    /// followed by counts per operation and addressing mode.
+    pub fn report<W: Word>(&self, memory: &Memory<W>, limit: usize) -> String {
+        let percent = |count: u64| 100.0 * count as f64 / self.steps.max(1) as f64;
+        let mut report = String::new();
+
+        writeln!(report, "{} instructions executed", self.steps).unwrap();
+        writeln!(report, "\nHottest instructions:").unwrap();
+
+        for (pc, count) in sorted(&self.pcs).into_iter().take(limit) {
+            let window: Vec<Unit> = (pc..pc + 4).map(|address| memory[address].to_unit_saturating()).collect();
+            let instruction = Instruction::decode(&window, 0)
+                .map_or_else(|| format!(".data {}", window[0]), |instruction| instruction.to_string());
+
+            writeln!(report, "{:>12} {:>6.2}% {:>6}  {}", count, percent(count), pc, instruction).unwrap();
+        }
+
+        for (title, counts) in &[("Hottest reads:", &self.reads), ("Hottest writes:", &self.writes)] {
+            writeln!(report, "\n{}", title).unwrap();
+
+            for (address, count) in sorted(counts).into_iter().take(limit) {
+                writeln!(report, "{:>12} {:>6}  {}", count, address, memory[address]).unwrap();
+            }
+        }
+
+        writeln!(report, "\nOperations:").unwrap();
+
+        for (op, count) in sorted(&self.ops) {
+            writeln!(report, "{:>12} {:>6.2}%  {}", count, percent(count), op.mnemonic()).unwrap();
+        }
+
+        writeln!(report, "\nAddressing modes:").unwrap();
+
+        for (mode, count) in sorted(&self.modes) {
+            writeln!(report, "{:>12}  {:?}", count, mode).unwrap();
+        }
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assemble, Computer};
+
+    #[test]
+    fn test_profile() {
+        let memory = assemble("
+            loop:
+                ADD [n] #-1 -> [n]
+                JNZ [n] #loop
+                HALT
+            n:  .data 3
+        ").unwrap();
+
+        let mut computer = Computer::new(&memory, None);
+        computer.start_profiling();
+        computer.run().unwrap();
+        let profile = computer.stop_profiling().unwrap();
+
+        assert_eq!(profile.steps, 7);
+        assert_eq!(sorted(&profile.pcs), vec![(0, 3), (4, 3), (7, 1)]);
+        assert_eq!(sorted(&profile.ops), vec![(Op::Add, 3), (Op::JumpNZ, 3), (Op::Halt, 1)]);
+        assert_eq!((profile.reads[&8], profile.writes[&8]), (6, 3));
+        assert_eq!(profile.folded(), "L0 7\n");
+        assert!(profile.report(computer.get_memory(), 1).contains("      0  ADD [8] #-1 -> [8]"));
+    }
+}
@@ -0,0 +1,381 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::{Computer, IntcodeError, OutputDevice, State, Unit, Word};
+
+/// The number of instructions a machine runs between checks for a shutdown.
+const CHUNK: u64 = 100_000;
+
+enum Message<W> {
+    Value(W),
+    Shutdown,
+}
+
+/// What every machine is doing, kept under one lock so that a machine can
+/// tell whether it is the last one to start waiting. `pending` counts values
+/// sent to a machine that it has not received yet.
+struct Status<W> {
+    senders: Vec<Sender<Message<W>>>,
+    waiting: Vec<bool>,
+    halted: Vec<bool>,
+    pending: Vec<usize>,
+    shutdown: bool,
+    deadlock: bool,
+}
+
+impl<W> Status<W> {
+    /// True if some machine is still alive but every live machine is waiting
+    /// for input that nobody has sent.
+    fn is_deadlocked(&self) -> bool {
+        let mut live = (0..self.halted.len()).filter(|id| !self.halted[*id]).peekable();
+        live.peek().is_some() && live.all(|id| self.waiting[id] && self.pending[id] == 0)
+    }
+
+    fn shutdown(&mut self) {
+        if !self.shutdown {
+            self.shutdown = true;
+            self.senders.iter().for_each(|sender| { let _ = sender.send(Message::Shutdown); });
+        }
+    }
+
+    fn check_deadlock(&mut self) {
+        if self.is_deadlocked() {
+            self.deadlock = true;
+            self.shutdown();
+        }
+    }
+}
+
+/// Sends output to the input channel of another machine.
+struct ChannelOutput<W> {
+    target: usize,
+    status: Arc<Mutex<Status<W>>>,
+}
+
+impl<W> OutputDevice<W> for ChannelOutput<W> {
+    fn write(&mut self, value: W) -> io::Result<()> {
+        let mut status = self.status.lock().unwrap();
+
+        if status.senders[self.target].send(Message::Value(value)).is_ok() {
+            status.pending[self.target] += 1;
+        }
+
+        Ok(())
+    }
+}
+
+enum Exit {
+    Halted,
+    Stopped,
+    Paused,
+    Failed(IntcodeError),
+}
+
+pub enum RuntimeError<W = Unit> {
+    /// Every machine that had not halted was waiting for input with none in
+    /// flight. `blocked` lists the ids of the waiting machines.
+    Deadlock { blocked: Vec<usize>, computers: Vec<Computer<W>> },
+    Machine { id: usize, error: IntcodeError },
+    /// Machine `id` was paused by a watchpoint, see `get_watch_event`.
+    Paused { id: usize, computers: Vec<Computer<W>> },
+    /// The NAT of a `Network` sent a packet to its own address.
+    NatSendToSelf { address: Unit },
+}
+
+impl<W> fmt::Debug for RuntimeError<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuntimeError::Deadlock { blocked, .. } => f.debug_struct("Deadlock").field("blocked", blocked).finish(),
+            RuntimeError::Machine { id, error } => f.debug_struct("Machine").field("id", id).field("error", error).finish(),
+            RuntimeError::Paused { id, .. } => f.debug_struct("Paused").field("id", id).finish(),
+            RuntimeError::NatSendToSelf { address } => f.debug_struct("NatSendToSelf").field("address", address).finish(),
+        }
+    }
+}
+
+impl<W> fmt::Display for RuntimeError<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuntimeError::Deadlock { blocked, .. } => write!(f, "Deadlock: machines {:?} are waiting for input", blocked),
+            RuntimeError::Machine { id, error } => write!(f, "Machine {}: {}", id, error),
+            RuntimeError::Paused { id, .. } => write!(f, "Machine {}: paused by a watchpoint", id),
+            RuntimeError::NatSendToSelf { address } => write!(f, "NAT at {} sent a packet to itself", address),
+        }
+    }
+}
+
+impl<W> Error for RuntimeError<W> {}
+
+/// Runs computers on threads of their own, with the output of a machine
+/// sent over a channel to the input of another. A machine that needs input
+/// blocks on its channel rather than returning `State::Blocked`.
+///
+/// Input given with `push_input` before a computer is added is read before
+/// anything from the channel. Output of a machine that is not connected
+/// stays in its output queue, and values that reach a machine after it has
+/// halted are left in its input queue.
+pub struct Runtime<W = Unit> {
+    computers: Vec<Computer<W>>,
+    connected: Vec<bool>,
+    receivers: Vec<Receiver<Message<W>>>,
+    status: Arc<Mutex<Status<W>>>,
+}
+
+impl<W: Word> Runtime<W> {
+    pub fn new() -> Runtime<W> {
+        Runtime {
+            computers: Vec::new(),
+            connected: Vec::new(),
+            receivers: Vec::new(),
+            status: Arc::new(Mutex::new(Status {
+                senders: Vec::new(),
+                waiting: Vec::new(),
+                halted: Vec::new(),
+                pending: Vec::new(),
+                shutdown: false,
+                deadlock: false,
+            })),
+        }
+    }
+
+    /// Adds a machine and returns its id.
+    pub fn add(&mut self, computer: Computer<W>) -> usize {
+        let (sender, receiver) = mpsc::channel();
+        let mut status = self.status.lock().unwrap();
+
+        status.senders.push(sender);
+        status.waiting.push(false);
+        status.halted.push(false);
+        status.pending.push(0);
+
+        self.computers.push(computer);
+        self.connected.push(false);
+        self.receivers.push(receiver);
+        self.computers.len() - 1
+    }
+
+    /// Sends all output of machine `from` to machine `to`.
+    pub fn connect(&mut self, from: usize, to: usize) {
+        assert!(to < self.computers.len(), "Unknown machine: {}", to);
+
+        self.computers[from].set_output_device(ChannelOutput { target: to, status: Arc::clone(&self.status) });
+        self.connected[from] = true;
+    }
+
+    fn run_machine(id: usize, mut computer: Computer<W>, receiver: &Receiver<Message<W>>, status: &Mutex<Status<W>>) -> (Computer<W>, Exit) {
+        loop {
+            match computer.run_for(CHUNK) {
+                Err(error) => {
+                    let mut status = status.lock().unwrap();
+                    status.halted[id] = true;
+                    status.shutdown();
+                    return (computer, Exit::Failed(error));
+                }
+                Ok(State::Halted) => {
+                    let mut status = status.lock().unwrap();
+                    status.halted[id] = true;
+                    status.check_deadlock();
+                    return (computer, Exit::Halted);
+                }
+                Ok(State::Paused) => {
+                    status.lock().unwrap().shutdown();
+                    return (computer, Exit::Paused);
+                }
+                Ok(State::OutOfFuel) => {
+                    if status.lock().unwrap().shutdown {
+                        return (computer, Exit::Stopped);
+                    }
+                }
+                Ok(state @ State::NotStarted) | Ok(state @ State::Running) => unreachable!("run_for stopped while {:?}", state),
+                Ok(State::Blocked) => {
+                    let mut locked = status.lock().unwrap();
+
+                    if locked.shutdown {
+                        return (computer, Exit::Stopped);
+                    }
+
+                    locked.waiting[id] = true;
+                    locked.check_deadlock();
+                    drop(locked);
+
+                    match receiver.recv() {
+                        Ok(Message::Value(value)) => {
+                            let mut status = status.lock().unwrap();
+                            status.waiting[id] = false;
+                            status.pending[id] -= 1;
+                            computer.push_input(value);
+                        }
+                        _ => return (computer, Exit::Stopped),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Runs all machines until they have halted. Stops every machine if one
+    /// of them fails or is paused by a watchpoint, or if they deadlock.
+    pub fn run(self) -> Result<Vec<Computer<W>>, RuntimeError<W>> {
+        let status = self.status;
+
+        let handles: Vec<_> = self.computers.into_iter()
+            .zip(self.receivers)
+            .enumerate()
+            .map(|(id, (computer, receiver))| {
+                let status = Arc::clone(&status);
+
+                thread::spawn(move || {
+                    let (computer, exit) = Runtime::run_machine(id, computer, &receiver, &status);
+                    (computer, receiver, exit)
+                })
+            })
+            .collect();
+
+        let finished: Vec<_> = handles.into_iter()
+            .map(|handle| handle.join().expect("Machine thread panicked"))
+            .collect();
+
+        let mut computers = Vec::new();
+        let mut blocked = Vec::new();
+        let mut failure = None;
+        let mut paused = None;
+
+        for (id, (mut computer, receiver, exit)) in finished.into_iter().enumerate() {
+            for message in receiver.try_iter() {
+                if let Message::Value(value) = message {
+                    computer.push_input(value);
+                }
+            }
+
+            if self.connected[id] {
+                computer.clear_devices();
+            }
+
+            match exit {
+                Exit::Halted => (),
+                Exit::Stopped => blocked.push(id),
+                Exit::Paused => { paused.get_or_insert(id); }
+                Exit::Failed(error) => { failure.get_or_insert(RuntimeError::Machine { id, error }); }
+            }
+
+            computers.push(computer);
+        }
+
+        let deadlock = status.lock().unwrap().deadlock;
+
+        match (failure, paused) {
+            (Some(error), _) => Err(error),
+            (None, Some(id)) => Err(RuntimeError::Paused { id, computers }),
+            (None, None) if deadlock => Err(RuntimeError::Deadlock { blocked, computers }),
+            (None, None) => Ok(computers),
+        }
+    }
+}
+
+impl<W: Word> Default for Runtime<W> {
+    fn default() -> Runtime<W> {
+        Runtime::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Access, WatchAction};
+
+    #[test]
+    fn test_feedback_loop() {
+        let memory: Vec<Unit> = vec![
+            3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1, 28, 1005, 28, 6, 99, 0, 0, 5
+        ];
+
+        let mut runtime = Runtime::new();
+
+        for (id, phase) in [9, 8, 7, 6, 5].iter().enumerate() {
+            let input = if id == 0 { vec![*phase, 0] } else { vec![*phase] };
+            runtime.add(Computer::new(&memory, Some(&input)));
+        }
+
+        for id in 0..5 {
+            runtime.connect(id, (id + 1) % 5);
+        }
+
+        let computers = runtime.run().unwrap();
+
+        assert_eq!(computers[0].get_input().back(), Some(&139629729));
+    }
+
+    #[test]
+    fn test_deadlock() {
+        // Reads a value and echoes it back
+        let echo: Vec<Unit> = vec![3, 7, 4, 7, 1105, 1, 0, 0];
+
+        let mut runtime = Runtime::new();
+        let a = runtime.add(Computer::new(&echo, Some(&vec![1])));
+        let b = runtime.add(Computer::new(&echo, None));
+        let c = runtime.add(Computer::new(&vec![99], None));
+        runtime.connect(a, b);
+
+        match runtime.run() {
+            Err(RuntimeError::Deadlock { blocked, computers }) => {
+                assert_eq!(blocked, vec![a, b]);
+                assert_eq!(computers[b].get_output().iter().copied().collect::<Vec<Unit>>(), vec![1]);
+                assert_eq!(*computers[c].get_state(), State::Halted);
+            }
+            result => panic!("Expected deadlock, got {:?}", result.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_failure_stops_running_machines() {
+        // The first machine loops forever
+        let mut runtime = Runtime::new();
+        runtime.add(Computer::new(&vec![1106, 0, 3, 1105, 1, 0], None));
+        let b = runtime.add(Computer::new(&vec![42], None));
+
+        match runtime.run() {
+            Err(RuntimeError::Machine { id, .. }) => assert_eq!(id, b),
+            result => panic!("Expected failure, got {:?}", result.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_paused() {
+        let echo: Vec<Unit> = vec![3, 7, 4, 7, 1105, 1, 0, 0];
+
+        let mut runtime = Runtime::new();
+        let mut computer = Computer::new(&vec![1101, 1, 2, 5, 99, 0], None);
+        computer.add_watchpoint(5..6, Access::Write, |_| WatchAction::Pause);
+        let a = runtime.add(computer);
+        let b = runtime.add(Computer::new(&echo, None));
+        let c = runtime.add(Computer::new(&vec![1106, 0, 3, 1105, 1, 0], None));
+
+        match runtime.run() {
+            Err(RuntimeError::Paused { id, computers }) => {
+                assert_eq!(id, a);
+                assert_eq!(*computers[a].get_state(), State::Paused);
+                assert_eq!(computers[a].get_watch_event().map(|event| event.new), Some(3));
+                assert_eq!(*computers[b].get_state(), State::Blocked);
+                assert_eq!(*computers[c].get_state(), State::OutOfFuel);
+            }
+            result => panic!("Expected pause, got {:?}", result.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_out_of_fuel() {
+        // Counts down from 3, then outputs 0
+        let mut computer = Computer::new(&vec![1001, 10, -1, 10, 1005, 10, 0, 4, 10, 99, 3], None);
+        assert_eq!(computer.run_for(2), Ok(State::OutOfFuel));
+
+        let mut runtime = Runtime::new();
+        runtime.add(computer);
+
+        let computers = runtime.run().unwrap();
+        assert_eq!(*computers[0].get_state(), State::Halted);
+        assert_eq!(computers[0].get_output().iter().copied().collect::<Vec<Unit>>(), vec![0]);
+    }
+}
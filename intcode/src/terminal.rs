@@ -0,0 +1,120 @@
+use crate::{Computer, IntcodeError, State, Unit, Word};
+
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum Newline {
+    #[default]
+    Lf,
+    /// Lines sent end in CR LF, and every CR in the output is dropped.
+    CrLf,
+}
+
+/// Talks to a text-mode program, such as those of days 17, 21 and 25, a
+/// line at a time. Output in the ASCII range is text, and anything else,
+/// typically the puzzle answer, is set aside as an answer.
+pub struct AsciiTerminal<W = Unit> {
+    computer: Computer<W>,
+    newline: Newline,
+    answers: Vec<W>,
+}
+
+impl<W: Word> AsciiTerminal<W> {
+    pub fn new(computer: Computer<W>) -> AsciiTerminal<W> {
+        AsciiTerminal { computer, newline: Newline::default(), answers: Vec::new() }
+    }
+
+    pub fn computer(&self) -> &Computer<W> {
+        &self.computer
+    }
+
+    pub fn computer_mut(&mut self) -> &mut Computer<W> {
+        &mut self.computer
+    }
+
+    pub fn into_computer(self) -> Computer<W> {
+        self.computer
+    }
+
+    pub fn set_newline(&mut self, newline: Newline) {
+        self.newline = newline;
+    }
+
+    pub fn is_halted(&self) -> bool {
+        *self.computer.get_state() == State::Halted
+    }
+
+    /// Queues `line` as input, followed by a newline.
+    pub fn send_line(&mut self, line: &str) {
+        match self.newline {
+            Newline::Lf => self.computer.println(line.to_string()),
+            Newline::CrLf => {
+                line.chars().for_each(|c| self.computer.print(c));
+                self.computer.print('\r');
+                self.computer.print('\n');
+            }
+        }
+    }
+
+    /// Runs the program until it waits for input or halts, and returns the
+    /// text it printed along the way.
+    pub fn read_until_prompt(&mut self) -> Result<String, IntcodeError> {
+        self.computer.run()?;
+
+        let mut text = String::new();
+
+        while let Some(value) = self.computer.pop_output() {
+            match value.to_unit() {
+                Some(13) if self.newline == Newline::CrLf => (),
+                Some(c) if (0..128).contains(&c) => text.push(c as u8 as char),
+                _ => self.answers.push(value),
+            }
+        }
+
+        Ok(text)
+    }
+
+    /// Every value outside the ASCII range output so far, oldest first.
+    pub fn answers(&self) -> &[W] {
+        &self.answers
+    }
+
+    /// The last value outside the ASCII range output so far.
+    pub fn answer(&self) -> Option<&W> {
+        self.answers.last()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assemble;
+
+    #[test]
+    fn test_terminal() {
+        let memory = assemble("
+                OUT #72
+                OUT #63
+                OUT #13
+                OUT #10
+                IN -> [c]
+                OUT [c]
+                IN -> [c]
+                OUT [c]
+                OUT #1000
+                HALT
+            c:  .data 0
+        ").unwrap();
+
+        let mut terminal = AsciiTerminal::new(Computer::new(&memory, None));
+
+        assert_eq!(terminal.read_until_prompt(), Ok(String::from("H?\r\n")));
+        assert!(!terminal.is_halted());
+
+        terminal.set_newline(Newline::CrLf);
+        terminal.send_line("x");
+
+        assert_eq!(terminal.read_until_prompt(), Ok(String::from("x")));
+        assert!(terminal.is_halted());
+        assert_eq!(terminal.answers(), &[1000]);
+        assert_eq!(terminal.answer(), Some(&1000));
+    }
+}
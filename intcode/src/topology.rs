@@ -0,0 +1,175 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{Computer, RuntimeError, State, Unit, Word};
+
+/// A graph of computers where edges carry the output of one node to the
+/// input of another. A node may feed several nodes, each getting a copy of
+/// every value, several nodes may feed the same node, and cycles are fine.
+///
+/// Nodes are run in a deterministic round robin, each until it blocks or
+/// halts, and values are routed as soon as a node stops.
+pub struct Topology<W = Unit> {
+    nodes: Vec<Computer<W>>,
+    edges: Vec<Vec<usize>>,
+    taps: BTreeSet<usize>,
+}
+
+impl<W: Word> Topology<W> {
+    pub fn new() -> Topology<W> {
+        Topology { nodes: Vec::new(), edges: Vec::new(), taps: BTreeSet::new() }
+    }
+
+    /// Adds a node and returns its id. Initial inputs are given to the
+    /// computer before it is added.
+    pub fn add(&mut self, computer: Computer<W>) -> usize {
+        self.nodes.push(computer);
+        self.edges.push(Vec::new());
+        self.nodes.len() - 1
+    }
+
+    pub fn connect(&mut self, from: usize, to: usize) {
+        assert!(to < self.nodes.len(), "Unknown node: {}", to);
+
+        self.edges[from].push(to);
+    }
+
+    /// Also records the output of a node that has outgoing edges, such as
+    /// the last amplifier in a feedback loop.
+    pub fn tap(&mut self, node: usize) {
+        self.taps.insert(node);
+    }
+
+    pub fn node(&self, id: usize) -> &Computer<W> {
+        &self.nodes[id]
+    }
+
+    /// Nodes whose output is returned by `run`: those without outgoing
+    /// edges and those tapped.
+    pub fn sinks(&self) -> BTreeSet<usize> {
+        (0..self.nodes.len())
+            .filter(|id| self.edges[*id].is_empty() || self.taps.contains(id))
+            .collect()
+    }
+
+    /// Runs the graph until every node has halted or is waiting for input
+    /// that no other node will send, and returns the output of each sink.
+    ///
+    /// A node paused by a watchpoint stops the run with `RuntimeError::Paused`,
+    /// after its output has been routed, with copies of the nodes. The
+    /// topology keeps the nodes, so running it again resumes the paused node
+    /// and returns the sink output from then on.
+    pub fn run(&mut self) -> Result<BTreeMap<usize, Vec<W>>, RuntimeError<W>> {
+        let sinks = self.sinks();
+        let mut outputs: BTreeMap<usize, Vec<W>> = sinks.iter().map(|id| (*id, Vec::new())).collect();
+
+        loop {
+            let mut routed = false;
+
+            for id in 0..self.nodes.len() {
+                let paused = match self.nodes[id].get_state() {
+                    State::Halted => false,
+                    _ => self.nodes[id].run().map_err(|error| RuntimeError::Machine { id, error })? == State::Paused,
+                };
+
+                while let Some(value) = self.nodes[id].pop_output() {
+                    for to in &self.edges[id] {
+                        self.nodes[*to].push_input(value.clone());
+                        routed = true;
+                    }
+
+                    if let Some(output) = outputs.get_mut(&id) {
+                        output.push(value);
+                    }
+                }
+
+                if paused {
+                    return Err(RuntimeError::Paused { id, computers: self.nodes.clone() });
+                }
+            }
+
+            if !routed {
+                return Ok(outputs);
+            }
+        }
+    }
+}
+
+impl<W: Word> Default for Topology<W> {
+    fn default() -> Topology<W> {
+        Topology::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Access, WatchAction};
+
+    #[test]
+    fn test_feedback_loop() {
+        let memory: Vec<Unit> = vec![
+            3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1, 28, 1005, 28, 6, 99, 0, 0, 5
+        ];
+
+        let mut topology = Topology::new();
+        let amplifiers: Vec<usize> = [vec![9, 0], vec![8], vec![7], vec![6], vec![5]].iter()
+            .map(|input| topology.add(Computer::new(&memory, Some(input))))
+            .collect();
+
+        for (from, to) in amplifiers.iter().zip(amplifiers.iter().cycle().skip(1)) {
+            topology.connect(*from, *to);
+        }
+
+        topology.tap(amplifiers[4]);
+
+        let outputs = topology.run().unwrap();
+        assert_eq!(outputs[&amplifiers[4]].last(), Some(&139629729));
+    }
+
+    #[test]
+    fn test_paused() {
+        let mut computer = Computer::new(&vec![1101, 1, 2, 7, 4, 7, 99, 0], None);
+        computer.add_watchpoint(7..8, Access::Write, |_| WatchAction::Pause);
+
+        let mut topology = Topology::new();
+        let source = topology.add(computer);
+        let sink = topology.add(Computer::new(&vec![3, 5, 4, 5, 99, 0], None));
+        topology.connect(source, sink);
+
+        match topology.run() {
+            Err(RuntimeError::Paused { id, computers }) => {
+                assert_eq!(id, source);
+                assert_eq!(computers[source].get_watch_event().map(|event| event.new), Some(3));
+                assert_eq!(*topology.node(source).get_state(), State::Paused);
+            }
+            result => panic!("Expected pause, got {:?}", result.map(|_| ())),
+        }
+
+        let outputs = topology.run().unwrap();
+        assert_eq!(outputs[&sink], vec![3]);
+        assert_eq!(*topology.node(source).get_state(), State::Halted);
+    }
+
+    #[test]
+    fn test_fan_out_and_fan_in() {
+        // Outputs each input value doubled, until it reads a 0
+        let doubler: Vec<Unit> = vec![3, 15, 1006, 15, 14, 1002, 15, 2, 15, 4, 15, 1105, 1, 0, 99, 0];
+
+        let mut topology = Topology::new();
+        let source = topology.add(Computer::new(&doubler, Some(&vec![1, 2, 0])));
+        let left = topology.add(Computer::new(&doubler, None));
+        let right = topology.add(Computer::new(&doubler, None));
+        let sink = topology.add(Computer::new(&doubler, None));
+
+        topology.connect(source, left);
+        topology.connect(source, right);
+        topology.connect(left, sink);
+        topology.connect(right, sink);
+
+        let outputs = topology.run().unwrap();
+        assert_eq!(outputs.keys().copied().collect::<Vec<usize>>(), vec![sink]);
+        assert_eq!(outputs[&sink], vec![8, 16, 8, 16]);
+        assert_eq!(*topology.node(source).get_state(), State::Halted);
+        assert_eq!(*topology.node(sink).get_state(), State::Blocked);
+    }
+}
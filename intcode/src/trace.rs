@@ -0,0 +1,259 @@
+use std::fmt::Display;
+use std::str::FromStr;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::{parse_memory, AddressingMode, Op, Unit};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MemoryWrite<W = Unit> {
+    pub address: usize,
+    pub old: W,
+    pub new: W,
+}
+
+/// One executed instruction. `values` holds the resolved parameters, with
+/// the destination given as the address written to, and `relative_base` is
+/// the base the parameters were resolved against.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TraceRecord<W = Unit> {
+    pub step: u64,
+    pub pc: usize,
+    pub opcode: Unit,
+    pub op: Op,
+    pub modes: Vec<AddressingMode>,
+    pub values: Vec<W>,
+    pub write: Option<MemoryWrite<W>>,
+    pub relative_base: Unit,
+}
+
+fn mode_name(mode: &AddressingMode) -> &'static str {
+    match mode {
+        AddressingMode::Absolute => "absolute",
+        AddressingMode::Immediate => "immediate",
+        AddressingMode::Relative => "relative",
+    }
+}
+
+fn mode_from_name(name: &str) -> Option<AddressingMode> {
+    match name {
+        "absolute" => Some(AddressingMode::Absolute),
+        "immediate" => Some(AddressingMode::Immediate),
+        "relative" => Some(AddressingMode::Relative),
+        _ => None,
+    }
+}
+
+/// The raw value of `key` in a flat JSON object as written by `to_json`,
+/// where lists and objects never nest.
+fn json_value<'j>(json: &'j str, key: &str) -> Option<&'j str> {
+    let start = json.find(&format!("\"{}\":", key))? + key.len() + 3;
+    let rest = &json[start..];
+
+    let end = match rest.chars().next()? {
+        '[' => rest.find(']')? + 1,
+        '{' => rest.find('}')? + 1,
+        _ => rest.find([',', '}'])?,
+    };
+
+    Some(&rest[..end])
+}
+
+fn json_list(json: &str) -> Option<Vec<&str>> {
+    let items = json.strip_prefix('[')?.strip_suffix(']')?;
+    Some(items.split(',').filter(|item| !item.is_empty()).collect())
+}
+
+fn json_number<T: FromStr>(json: &str, key: &str) -> Option<T> {
+    json_value(json, key)?.parse().ok()
+}
+
+fn json_string(json: &str) -> Option<&str> {
+    json.strip_prefix('"')?.strip_suffix('"')
+}
+
+fn to_json_list<T>(values: &[T], format: impl Fn(&T) -> String) -> String {
+    let values: Vec<String> = values.iter().map(format).collect();
+    format!("[{}]", values.join(","))
+}
+
+impl<W: Display> TraceRecord<W> {
+    pub fn to_json(&self) -> String {
+        let write = match &self.write {
+            Some(write) => format!("{{\"address\":{},\"old\":{},\"new\":{}}}", write.address, write.old, write.new),
+            None => String::from("null"),
+        };
+
+        format!(
+            "{{\"step\":{},\"pc\":{},\"opcode\":{},\"op\":\"{}\",\"modes\":{},\"values\":{},\"write\":{},\"rb\":{}}}",
+            self.step,
+            self.pc,
+            self.opcode,
+            self.op.mnemonic(),
+            to_json_list(&self.modes, |mode| format!("\"{}\"", mode_name(mode))),
+            to_json_list(&self.values, |value| value.to_string()),
+            write,
+            self.relative_base,
+        )
+    }
+}
+
+impl<W: FromStr> TraceRecord<W> {
+    /// Parses a line written by `to_json`, or returns `None` if it is not one.
+    pub fn from_json(line: &str) -> Option<TraceRecord<W>> {
+        let word = |json: &str| json.parse().ok();
+
+        let write = match json_value(line, "write")? {
+            "null" => None,
+            write => Some(MemoryWrite {
+                address: json_number(write, "address")?,
+                old: word(json_value(write, "old")?)?,
+                new: word(json_value(write, "new")?)?,
+            }),
+        };
+
+        let op = Op::from_mnemonic(json_string(json_value(line, "op")?)?)?;
+        let modes = json_list(json_value(line, "modes")?)?.into_iter()
+            .map(|mode| mode_from_name(json_string(mode)?))
+            .collect::<Option<Vec<AddressingMode>>>()?;
+        let values = json_list(json_value(line, "values")?)?.into_iter().map(word).collect::<Option<Vec<W>>>()?;
+
+        if modes.len() != op.num_params().0 || values.len() != op.num_params().0 {
+            return None;
+        }
+
+        Some(TraceRecord {
+            step: json_number(line, "step")?,
+            pc: json_number(line, "pc")?,
+            opcode: json_number(line, "opcode")?,
+            op,
+            modes,
+            values,
+            write,
+            relative_base: json_number(line, "rb")?,
+        })
+    }
+}
+
+/// Parses a trace as written by `TraceSink`, one record per line.
+pub fn parse_trace<W: FromStr>(trace: &str) -> io::Result<Vec<TraceRecord<W>>> {
+    trace.lines()
+        .map(|line| TraceRecord::from_json(line)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid trace record: {}", line))))
+        .collect()
+}
+
+/// Reads the `PROGRAM [TRACE]` arguments of the tools that analyze a
+/// program, optionally with a trace of a run: the program and the trace
+/// records, if any.
+pub fn read_program_and_trace(mut args: impl Iterator<Item = String>) -> io::Result<(Vec<Unit>, Vec<TraceRecord>)> {
+    let read = |path: &str| fs::read_to_string(path)
+        .map_err(|e| io::Error::new(e.kind(), format!("Failed to read file: {}: {}", path, e)));
+
+    let path = args.next().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Missing program"))?;
+    let memory = parse_memory(&read(&path)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("Failed to parse program: {}", e)))?;
+
+    let trace = match args.next() {
+        Some(path) => parse_trace(&read(&path)?)?,
+        None => Vec::new(),
+    };
+
+    Ok((memory, trace))
+}
+
+struct Sink {
+    writer: Box<dyn Write + Send>,
+    error: Option<io::Error>,
+}
+
+/// Destination for a JSON Lines execution trace, one object per executed
+/// instruction. Clones share the underlying writer, so a cloned `Computer`
+/// keeps tracing into the same stream.
+///
+/// Tracing never interrupts execution: the first write error stops the
+/// sink and is kept for `flush` to report.
+#[derive(Clone)]
+pub struct TraceSink {
+    sink: Arc<Mutex<Sink>>,
+}
+
+impl TraceSink {
+    pub fn new(writer: impl Write + Send + 'static) -> TraceSink {
+        TraceSink {
+            sink: Arc::new(Mutex::new(Sink { writer: Box::new(writer), error: None })),
+        }
+    }
+
+    pub fn create(path: impl AsRef<Path>) -> io::Result<TraceSink> {
+        Ok(TraceSink::new(BufWriter::new(File::create(path)?)))
+    }
+
+    pub fn record<W: Display>(&self, record: &TraceRecord<W>) {
+        let mut sink = self.sink.lock().unwrap();
+
+        if sink.error.is_none() {
+            if let Err(e) = writeln!(sink.writer, "{}", record.to_json()) {
+                sink.error = Some(e);
+            }
+        }
+    }
+
+    pub fn flush(&self) -> io::Result<()> {
+        let mut sink = self.sink.lock().unwrap();
+
+        match sink.error.take() {
+            Some(e) => Err(e),
+            None => sink.writer.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Computer;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_trace() {
+        let buffer = Buffer::default();
+        let mut computer = Computer::new(&vec![1002, 4, 3, 4, 33], None);
+        computer.set_trace(TraceSink::new(buffer.clone()));
+        computer.run().unwrap();
+
+        let trace = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = trace.lines().collect();
+
+        assert_eq!(lines, vec![
+            r#"{"step":1,"pc":0,"opcode":1002,"op":"MUL","modes":["absolute","immediate","absolute"],"values":[33,3,4],"write":{"address":4,"old":33,"new":99},"rb":0}"#,
+            r#"{"step":2,"pc":4,"opcode":99,"op":"HALT","modes":[],"values":[],"write":null,"rb":0}"#,
+        ]);
+        assert_eq!(computer.get_steps(), 2);
+
+        let record: TraceRecord = TraceRecord::from_json(lines[0]).unwrap();
+        assert_eq!(record.write, Some(MemoryWrite { address: 4, old: 33, new: 99 }));
+        assert_eq!(record.to_json(), lines[0]);
+        assert_eq!(TraceRecord::<Unit>::from_json(lines[1]).unwrap().to_json(), lines[1]);
+        assert_eq!(TraceRecord::<Unit>::from_json("{}"), None);
+        assert_eq!(TraceRecord::<Unit>::from_json(&lines[0].replace("[33,3,4]", "[33,3]")), None);
+        assert_eq!(TraceRecord::<Unit>::from_json(&lines[1].replace("\"modes\":[]", "\"modes\":[\"absolute\"]")), None);
+
+        assert_eq!(parse_trace::<Unit>(&trace).unwrap().len(), 2);
+        assert_eq!(parse_trace::<Unit>(&format!("{}{{}}\n", trace)).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,114 @@
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+use crate::Unit;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Access {
+    Read,
+    Write,
+    /// Watches both reads and writes. Events are never `ReadWrite`.
+    ReadWrite,
+}
+
+impl Access {
+    fn matches(self, access: Access) -> bool {
+        self == Access::ReadWrite || self == access
+    }
+}
+
+/// A watched memory access by the instruction at `pc`. For a read, `old`
+/// and `new` are both the value read.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct WatchEvent<W = Unit> {
+    pub watchpoint: usize,
+    pub pc: usize,
+    pub access: Access,
+    pub address: usize,
+    pub old: W,
+    pub new: W,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum WatchAction {
+    Continue,
+    /// Stops the computer with `State::Paused` once the instruction that
+    /// triggered the watchpoint has completed.
+    Pause,
+}
+
+type Handler<W> = Arc<Mutex<Box<dyn FnMut(&WatchEvent<W>) -> WatchAction + Send>>>;
+
+struct Watchpoint<W> {
+    id: usize,
+    addresses: Range<usize>,
+    access: Access,
+    handler: Handler<W>,
+}
+
+impl<W> Clone for Watchpoint<W> {
+    fn clone(&self) -> Watchpoint<W> {
+        Watchpoint { id: self.id, addresses: self.addresses.clone(), access: self.access, handler: Arc::clone(&self.handler) }
+    }
+}
+
+/// The watchpoints of a computer. Clones share their handlers, like the
+/// devices of a cloned computer.
+#[derive(Clone)]
+pub(crate) struct Watchpoints<W> {
+    watchpoints: Vec<Watchpoint<W>>,
+    next_id: usize,
+}
+
+impl<W> Default for Watchpoints<W> {
+    fn default() -> Watchpoints<W> {
+        Watchpoints { watchpoints: Vec::new(), next_id: 0 }
+    }
+}
+
+impl<W> Watchpoints<W> {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.watchpoints.is_empty()
+    }
+
+    pub(crate) fn add(
+        &mut self,
+        addresses: Range<usize>,
+        access: Access,
+        handler: impl FnMut(&WatchEvent<W>) -> WatchAction + Send + 'static,
+    ) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.watchpoints.push(Watchpoint { id, addresses, access, handler: Arc::new(Mutex::new(Box::new(handler))) });
+        id
+    }
+
+    pub(crate) fn remove(&mut self, id: usize) -> bool {
+        let len = self.watchpoints.len();
+        self.watchpoints.retain(|watchpoint| watchpoint.id != id);
+        self.watchpoints.len() != len
+    }
+
+    /// Calls the handler of every watchpoint covering the access, and
+    /// returns the first event whose handler asked to pause.
+    pub(crate) fn check(&self, pc: usize, access: Access, address: usize, old: &W, new: &W) -> Option<WatchEvent<W>>
+    where
+        W: Clone,
+    {
+        let mut pause = None;
+
+        for watchpoint in &self.watchpoints {
+            if !watchpoint.addresses.contains(&address) || !watchpoint.access.matches(access) {
+                continue;
+            }
+
+            let event = WatchEvent { watchpoint: watchpoint.id, pc, access, address, old: old.clone(), new: new.clone() };
+
+            if (watchpoint.handler.lock().unwrap())(&event) == WatchAction::Pause && pause.is_none() {
+                pause = Some(event);
+            }
+        }
+
+        pause
+    }
+}
@@ -0,0 +1,130 @@
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
+use std::ops::{Add, Mul};
+use std::str::FromStr;
+
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+
+use crate::Unit;
+
+/// How `Add` and `Mul` handle results that do not fit in a word.
+///
+/// Computers trap by default, so overflow stops the program with an error
+/// instead of wrapping or panicking depending on the build profile. Days
+/// that rely on wrapping have to ask for it with `set_arithmetic`.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub enum Arithmetic {
+    Wrapping,
+    Saturating,
+    /// Stops the computer with `IntcodeError::Overflow`.
+    #[default]
+    Trapping,
+}
+
+/// A value in Intcode memory.
+///
+/// Fixed width words overflow according to the computer's `Arithmetic`, so
+/// `i32` and `i64` are cheaper but may overflow, while `BigInt` grows as
+/// needed and never does. Addresses and opcodes are always handled as `Unit`.
+pub trait Word:
+    Clone + Default + Debug + Display + FromStr + Eq + Ord + Hash + Send + Sync + 'static
+    + Add<Output = Self> + Mul<Output = Self>
+{
+    /// Converts from `Unit`, truncating if the value does not fit.
+    fn from_unit(value: Unit) -> Self;
+
+    fn to_unit(&self) -> Option<Unit>;
+
+    fn checked_add(&self, rhs: &Self) -> Option<Self>;
+    fn checked_mul(&self, rhs: &Self) -> Option<Self>;
+    fn wrapping_add(&self, rhs: &Self) -> Self;
+    fn wrapping_mul(&self, rhs: &Self) -> Self;
+    fn saturating_add(&self, rhs: &Self) -> Self;
+    fn saturating_mul(&self, rhs: &Self) -> Self;
+
+    /// Converts to `Unit`, clamping values that do not fit.
+    fn to_unit_saturating(&self) -> Unit {
+        match self.to_unit() {
+            Some(value) => value,
+            None if *self < Self::default() => Unit::MIN,
+            None => Unit::MAX,
+        }
+    }
+}
+
+macro_rules! impl_word {
+    ($($t:ty),*) => {
+        $(
+            impl Word for $t {
+                fn from_unit(value: Unit) -> $t {
+                    value as $t
+                }
+
+                fn to_unit(&self) -> Option<Unit> {
+                    Some(*self as Unit)
+                }
+
+                fn checked_add(&self, rhs: &$t) -> Option<$t> {
+                    <$t>::checked_add(*self, *rhs)
+                }
+
+                fn checked_mul(&self, rhs: &$t) -> Option<$t> {
+                    <$t>::checked_mul(*self, *rhs)
+                }
+
+                fn wrapping_add(&self, rhs: &$t) -> $t {
+                    <$t>::wrapping_add(*self, *rhs)
+                }
+
+                fn wrapping_mul(&self, rhs: &$t) -> $t {
+                    <$t>::wrapping_mul(*self, *rhs)
+                }
+
+                fn saturating_add(&self, rhs: &$t) -> $t {
+                    <$t>::saturating_add(*self, *rhs)
+                }
+
+                fn saturating_mul(&self, rhs: &$t) -> $t {
+                    <$t>::saturating_mul(*self, *rhs)
+                }
+            }
+        )*
+    };
+}
+
+impl_word!(i32, i64, i128);
+
+impl Word for BigInt {
+    fn from_unit(value: Unit) -> BigInt {
+        BigInt::from(value)
+    }
+
+    fn to_unit(&self) -> Option<Unit> {
+        self.to_i128()
+    }
+
+    fn checked_add(&self, rhs: &BigInt) -> Option<BigInt> {
+        Some(self + rhs)
+    }
+
+    fn checked_mul(&self, rhs: &BigInt) -> Option<BigInt> {
+        Some(self * rhs)
+    }
+
+    fn wrapping_add(&self, rhs: &BigInt) -> BigInt {
+        self + rhs
+    }
+
+    fn wrapping_mul(&self, rhs: &BigInt) -> BigInt {
+        self * rhs
+    }
+
+    fn saturating_add(&self, rhs: &BigInt) -> BigInt {
+        self + rhs
+    }
+
+    fn saturating_mul(&self, rhs: &BigInt) -> BigInt {
+        self * rhs
+    }
+}